@@ -0,0 +1,408 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::path::{Path, PathBuf};
+
+use level_format::{known_object, Level, LevelObject, Point, KNOWN_OBJECTS, TILE_HEIGHT, TILE_WIDTH};
+
+// Share the game's sprites rather than keeping a copy for the editor
+const ASSET_FOLDER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets");
+
+const PALETTE_WIDTH: f32 = 220.0;
+const SCALE_STEP: f32 = 0.1;
+const MIN_SCALE: f32 = 0.1;
+
+const TEXT_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const PALETTE_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+const SELECTED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
+
+const HIT_BOX_COLOR: Color = Color::RED;
+const SELECTED_HIT_BOX_COLOR: Color = Color::YELLOW;
+
+#[derive(Resource)]
+struct EditorLevel {
+    level: Level,
+    path: PathBuf,
+}
+
+#[derive(Clone, PartialEq, Default)]
+enum Tool {
+    #[default]
+    Select,
+    Place(String),
+    HitBox,
+}
+
+#[derive(Resource, Default)]
+struct EditorState {
+    tool: Tool,
+    selected: Option<usize>,
+    dragging: bool,
+    drag_offset: Vec2,
+    status: String,
+}
+
+#[derive(Component)]
+struct EditorObject(usize);
+
+#[derive(Component)]
+struct PaletteButton(Tool);
+
+#[derive(Component)]
+struct StatusText;
+
+pub fn run(path: PathBuf, level: Level) {
+    App::new()
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                title: format!("Level Builder - {}", path.display()),
+                resolution: (TILE_WIDTH + PALETTE_WIDTH, TILE_HEIGHT).into(),
+                ..default()
+            }),
+            ..default()
+        }).set(AssetPlugin {
+            asset_folder: ASSET_FOLDER.to_string(),
+            ..default()
+        }))
+        .insert_resource(EditorLevel { level, path })
+        .init_resource::<EditorState>()
+        .add_systems(Startup, editor_setup)
+        .add_systems(Update, (
+            palette_system,
+            keyboard_system,
+            canvas_system,
+            scale_system,
+            sync_objects,
+            draw_hit_boxes,
+            update_status,
+        ).chain())
+        .run();
+}
+
+// The tile is drawn centred on the origin, the same as the game draws the first screen
+fn tile_to_world(point: Point) -> Vec2 {
+    Vec2::new(point.x - TILE_WIDTH / 2.0, point.y - TILE_HEIGHT / 2.0)
+}
+
+fn world_to_tile(world: Vec2) -> Point {
+    Point::new((world.x + TILE_WIDTH / 2.0).round(), (world.y + TILE_HEIGHT / 2.0).round())
+}
+
+fn object_texture(asset_server: &AssetServer, description: &str) -> Handle<Image> {
+    asset_server.load(Path::new("images").join("objects").join(format!("{}.png", description)))
+}
+
+fn cursor_in_tile(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Point> {
+    let window = windows.get_single().ok()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let cursor = window.cursor_position()?;
+    let world = camera.viewport_to_world_2d(camera_transform, cursor)?;
+    Some(world_to_tile(world))
+}
+
+fn object_contains(object: &LevelObject, point: Point) -> bool {
+    let half = Vec2::new(object.hit_box.x, object.hit_box.y) * object.scale / 2.0;
+    (point.x - object.location.x).abs() <= half.x && (point.y - object.location.y).abs() <= half.y
+}
+
+fn editor_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // Shift the camera so the palette sits to the right of the tile rather than over it
+    let mut camera = Camera2dBundle::default();
+    camera.transform.translation.x = PALETTE_WIDTH / 2.0;
+    commands.spawn(camera);
+
+    commands.spawn(SpriteBundle {
+        texture: asset_server.load(Path::new("images").join("scenery").join("street_scene.png")),
+        transform: Transform::from_xyz(0.0, 0.0, -1.0),
+        ..default()
+    });
+
+    let button_style = Style {
+        width: Val::Px(PALETTE_WIDTH - 20.0),
+        height: Val::Px(40.0),
+        margin: UiRect::all(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let text_style = TextStyle {
+        font_size: 24.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    let mut tools = vec![("Select".to_string(), Tool::Select), ("Hit box".to_string(), Tool::HitBox)];
+    for object in KNOWN_OBJECTS.iter() {
+        tools.push((object.description.to_string(), Tool::Place(object.description.to_string())));
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(0.0),
+                top: Val::Px(0.0),
+                width: Val::Px(PALETTE_WIDTH),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: PALETTE_COLOR.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for (label, tool) in tools {
+                parent
+                    .spawn(ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(PaletteButton(tool))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                    });
+            }
+
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: TEXT_COLOR,
+                        ..default()
+                    },
+                ).with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }))
+                .insert(StatusText);
+        });
+}
+
+fn palette_system(
+    mut buttons: Query<(&Interaction, &PaletteButton, &mut BackgroundColor)>,
+    mut state: ResMut<EditorState>,
+) {
+    for (interaction, button, _) in buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            state.tool = button.0.clone();
+            state.dragging = false;
+        }
+    }
+
+    for (interaction, button, mut color) in buttons.iter_mut() {
+        *color = if button.0 == state.tool {
+            SELECTED_BUTTON.into()
+        } else if *interaction == Interaction::Hovered {
+            HOVERED_BUTTON.into()
+        } else {
+            NORMAL_BUTTON.into()
+        };
+    }
+}
+
+fn keyboard_system(
+    keys: Res<Input<KeyCode>>,
+    mut editor: ResMut<EditorLevel>,
+    mut state: ResMut<EditorState>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        state.tool = Tool::Select;
+    }
+    if keys.just_pressed(KeyCode::H) {
+        state.tool = Tool::HitBox;
+    }
+
+    if keys.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        if let Some(index) = state.selected.take() {
+            let removed = editor.level.objects.remove(index);
+            state.status = format!("Removed {}", removed.description);
+        }
+    }
+
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keys.just_pressed(KeyCode::S) {
+        state.status = match editor.level.save(&editor.path) {
+            Ok(()) => format!("Saved {}", editor.path.display()),
+            Err(err) => format!("Save failed: {}", err),
+        };
+    }
+}
+
+fn canvas_system(
+    buttons: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    palette: Query<&Interaction, With<PaletteButton>>,
+    mut editor: ResMut<EditorLevel>,
+    mut state: ResMut<EditorState>,
+) {
+    if buttons.just_released(MouseButton::Left) {
+        state.dragging = false;
+        if state.tool == Tool::HitBox {
+            state.tool = Tool::Select;
+        }
+    }
+
+    // Clicks on the palette are handled by the palette
+    if palette.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+    let Some(cursor) = cursor_in_tile(&windows, &cameras) else {
+        return;
+    };
+    let cursor_vec = Vec2::new(cursor.x, cursor.y);
+
+    match state.tool.clone() {
+        Tool::Select => {
+            if buttons.just_pressed(MouseButton::Left) {
+                // Pick the top most object, which is the last one drawn
+                state.selected = editor.level.objects.iter().rposition(|object| object_contains(object, cursor));
+                if let Some(index) = state.selected {
+                    let location = editor.level.objects[index].location;
+                    state.drag_offset = Vec2::new(location.x, location.y) - cursor_vec;
+                    state.dragging = true;
+                }
+            } else if state.dragging && buttons.pressed(MouseButton::Left) {
+                if let Some(index) = state.selected {
+                    let location = (cursor_vec + state.drag_offset).round();
+                    editor.level.objects[index].location = Point::new(location.x, location.y);
+                }
+            }
+        }
+        Tool::Place(description) => {
+            if buttons.just_pressed(MouseButton::Left) {
+                let hit_box = known_object(&description)
+                    .map(|known| known.hit_box)
+                    .unwrap_or(Point::new(50.0, 50.0));
+                editor.level.objects.push(LevelObject {
+                    description,
+                    location: cursor,
+                    hit_box,
+                    scale: 1.0,
+                });
+                // Switch straight back so the new object can be dragged into place
+                state.selected = Some(editor.level.objects.len() - 1);
+                state.tool = Tool::Select;
+            }
+        }
+        Tool::HitBox => {
+            // Drag out from the centre of the selected object to size its hit box
+            if let Some(index) = state.selected {
+                if buttons.pressed(MouseButton::Left) {
+                    let object = &mut editor.level.objects[index];
+                    let half = Vec2::new(cursor.x - object.location.x, cursor.y - object.location.y).abs();
+                    let hit_box = (half * 2.0 / object.scale).round().max(Vec2::ONE);
+                    object.hit_box = Point::new(hit_box.x, hit_box.y);
+                }
+            }
+        }
+    }
+}
+
+fn scale_system(
+    mut wheel: EventReader<MouseWheel>,
+    keys: Res<Input<KeyCode>>,
+    mut editor: ResMut<EditorLevel>,
+    state: Res<EditorState>,
+) {
+    let mut steps: f32 = wheel.iter().map(|event| event.y.signum()).sum();
+    if keys.just_pressed(KeyCode::Equals) {
+        steps += 1.0;
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        steps -= 1.0;
+    }
+
+    if steps == 0.0 {
+        return;
+    }
+    if let Some(index) = state.selected {
+        let object = &mut editor.level.objects[index];
+        object.scale = ((object.scale + steps * SCALE_STEP) * 10.0).round() / 10.0;
+        object.scale = object.scale.max(MIN_SCALE);
+    }
+}
+
+// Keep the sprites in line with the level, rebuilding them when objects are added or removed
+fn sync_objects(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    editor: Res<EditorLevel>,
+    mut sprites: Query<(Entity, &EditorObject, &mut Transform)>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    let objects = &editor.level.objects;
+    if sprites.iter().count() == objects.len() {
+        for (_, editor_object, mut transform) in sprites.iter_mut() {
+            let object = &objects[editor_object.0];
+            transform.translation = tile_to_world(object.location).extend(transform.translation.z);
+            transform.scale = Vec3::splat(object.scale);
+        }
+        return;
+    }
+
+    for (entity, _, _) in sprites.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (index, object) in objects.iter().enumerate() {
+        commands.spawn((
+            SpriteBundle {
+                texture: object_texture(&asset_server, &object.description),
+                // Later objects draw over earlier ones, matching the pick order
+                transform: Transform::from_translation(tile_to_world(object.location).extend(index as f32 * 0.01))
+                    .with_scale(Vec3::splat(object.scale)),
+                ..default()
+            },
+            EditorObject(index),
+        ));
+    }
+}
+
+fn draw_hit_boxes(mut gizmos: Gizmos, editor: Res<EditorLevel>, state: Res<EditorState>) {
+    for (index, object) in editor.level.objects.iter().enumerate() {
+        let color = if state.selected == Some(index) {
+            SELECTED_HIT_BOX_COLOR
+        } else {
+            HIT_BOX_COLOR
+        };
+        let size = Vec2::new(object.hit_box.x, object.hit_box.y) * object.scale;
+        gizmos.rect_2d(tile_to_world(object.location), 0.0, size, color);
+    }
+}
+
+fn update_status(
+    editor: Res<EditorLevel>,
+    state: Res<EditorState>,
+    mut texts: Query<&mut Text, With<StatusText>>,
+) {
+    let tool = match &state.tool {
+        Tool::Select => "Select".to_string(),
+        Tool::Place(description) => format!("Place {}", description),
+        Tool::HitBox => "Hit box".to_string(),
+    };
+    let selected = match state.selected.and_then(|index| editor.level.objects.get(index)) {
+        Some(object) => format!(
+            "{} at {}\nhit box {} scale {}",
+            object.description, object.location, object.hit_box, object.scale
+        ),
+        None => "Nothing selected".to_string(),
+    };
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!(
+            "Tile {}_{}\nTool: {}\n{}\n\nDrag to move\nWheel or +/- to scale\nH then drag for hit box\nDel to remove\nCtrl+S to save\n\n{}",
+            editor.level.x, editor.level.y, tool, selected, state.status
+        );
+    }
+}
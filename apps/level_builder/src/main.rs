@@ -1,3 +1,297 @@
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[cfg(feature = "editor")]
+mod editor;
+
+use level_format::{check_neighbours, find_tiles, known_object, Level, LevelEnemy, LevelObject, Point, CURRENT_VERSION};
+
+// Default to the levels the game loads, regardless of where we are run from
+const DEFAULT_LEVELS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/levels");
+
+#[derive(Parser)]
+#[command(name = "level_builder", about = "Create and edit the zombie game level tiles")]
+struct Cli {
+    /// Directory holding the x_y.json tile files
+    #[arg(long, global = true, default_value = DEFAULT_LEVELS_DIR)]
+    dir: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new, empty tile file
+    New {
+        x: i32,
+        y: i32,
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Add a piece of scenery to a tile
+    AddObject {
+        x: i32,
+        y: i32,
+        /// Name of the sprite in assets/images/objects, e.g. car
+        object: String,
+        #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+        at: Vec<f32>,
+        /// Defaults to the size of the sprite
+        #[arg(long, num_args = 2, value_names = ["W", "H"])]
+        hit_box: Option<Vec<f32>>,
+        #[arg(long, default_value_t = 1.0)]
+        scale: f32,
+    },
+    /// Move an existing piece of scenery
+    MoveObject {
+        x: i32,
+        y: i32,
+        index: usize,
+        #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+        to: Vec<f32>,
+    },
+    /// Remove a piece of scenery
+    RemoveObject {
+        x: i32,
+        y: i32,
+        index: usize,
+    },
+    /// Add an enemy spawn to a tile
+    AddEnemy {
+        x: i32,
+        y: i32,
+        enemy: String,
+        #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+        at: Vec<f32>,
+        /// Waypoints to patrol, each given as X,Y
+        #[arg(long, value_parser = parse_point)]
+        route: Vec<Point>,
+    },
+    /// Move an existing enemy spawn
+    MoveEnemy {
+        x: i32,
+        y: i32,
+        index: usize,
+        #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+        to: Vec<f32>,
+    },
+    /// Remove an enemy spawn
+    RemoveEnemy {
+        x: i32,
+        y: i32,
+        index: usize,
+    },
+    /// Show what is in a tile, or every tile if none is given
+    List {
+        x: Option<i32>,
+        y: Option<i32>,
+    },
+    /// Open a tile in the visual editor, starting a new one if it doesn't exist yet
+    #[cfg(feature = "editor")]
+    Edit {
+        x: i32,
+        y: i32,
+    },
+    /// Check tiles for problems, or every tile if none is given
+    Validate {
+        x: Option<i32>,
+        y: Option<i32>,
+    },
+    /// Rewrite tiles in the current schema version, or every tile if none is given
+    Upgrade {
+        x: Option<i32>,
+        y: Option<i32>,
+    },
+}
+
+fn parse_point(value: &str) -> Result<Point, String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y but got '{}'", value))?;
+    let x = x.trim().parse::<f32>().map_err(|err| err.to_string())?;
+    let y = y.trim().parse::<f32>().map_err(|err| err.to_string())?;
+    Ok(Point::new(x, y))
+}
+
+fn tile_path(dir: &Path, x: i32, y: i32) -> PathBuf {
+    dir.join(Level::file_name(x, y))
+}
+
+fn load_tile(dir: &Path, x: i32, y: i32) -> Result<Level, String> {
+    Level::load(&tile_path(dir, x, y)).map_err(|err| err.to_string())
+}
+
+fn save_tile(dir: &Path, level: &Level) -> Result<(), String> {
+    level.save(&tile_path(dir, level.x, level.y)).map_err(|err| err.to_string())
+}
+
+fn selected_tiles(dir: &Path, x: Option<i32>, y: Option<i32>) -> Result<Vec<(i32, i32)>, String> {
+    match (x, y) {
+        (Some(x), Some(y)) => Ok(vec![(x, y)]),
+        (None, None) => find_tiles(dir).map_err(|err| err.to_string()),
+        _ => Err("give both x and y, or neither".to_string()),
+    }
+}
+
+fn point_arg(values: &[f32]) -> Point {
+    Point::new(values[0], values[1])
+}
+
+fn print_level(level: &Level) {
+    println!("Tile {}_{} (version {}): {}", level.x, level.y, level.version, level.description);
+    println!("  Objects:");
+    for (index, object) in level.objects.iter().enumerate() {
+        println!(
+            "    [{}] {} at {} hit box {} scale {}",
+            index, object.description, object.location, object.hit_box, object.scale
+        );
+    }
+    println!("  Enemies:");
+    for (index, enemy) in level.enemies.iter().enumerate() {
+        let route: Vec<String> = enemy.route.iter().map(|point| point.to_string()).collect();
+        println!("    [{}] {} at {} route [{}]", index, enemy.description, enemy.location, route.join(", "));
+    }
+    println!("  Characters:");
+    for (index, character) in level.characters.iter().enumerate() {
+        println!("    [{}] {} at {}", index, character.description, character.location);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    let dir = cli.dir;
+
+    match cli.command {
+        Command::New { x, y, description } => {
+            let path = tile_path(&dir, x, y);
+            if path.exists() {
+                return Err(format!("{} already exists", path.display()));
+            }
+            save_tile(&dir, &Level::new(x, y, &description))?;
+            println!("Created {}", path.display());
+        }
+        Command::AddObject { x, y, object, at, hit_box, scale } => {
+            let mut level = load_tile(&dir, x, y)?;
+            let hit_box = match hit_box {
+                Some(hit_box) => point_arg(&hit_box),
+                None => known_object(&object)
+                    .map(|known| known.hit_box)
+                    .ok_or_else(|| format!("unknown object '{}', give its --hit-box", object))?,
+            };
+            level.objects.push(LevelObject {
+                description: object,
+                location: point_arg(&at),
+                hit_box,
+                scale,
+            });
+            save_tile(&dir, &level)?;
+            println!("Added object {}", level.objects.len() - 1);
+        }
+        Command::MoveObject { x, y, index, to } => {
+            let mut level = load_tile(&dir, x, y)?;
+            let object = level
+                .objects
+                .get_mut(index)
+                .ok_or_else(|| format!("no object {} in tile {}_{}", index, x, y))?;
+            object.location = point_arg(&to);
+            save_tile(&dir, &level)?;
+        }
+        Command::RemoveObject { x, y, index } => {
+            let mut level = load_tile(&dir, x, y)?;
+            if index >= level.objects.len() {
+                return Err(format!("no object {} in tile {}_{}", index, x, y));
+            }
+            let removed = level.objects.remove(index);
+            save_tile(&dir, &level)?;
+            println!("Removed {}", removed.description);
+        }
+        Command::AddEnemy { x, y, enemy, at, route } => {
+            let mut level = load_tile(&dir, x, y)?;
+            level.enemies.push(LevelEnemy {
+                description: enemy,
+                location: point_arg(&at),
+                route,
+            });
+            save_tile(&dir, &level)?;
+            println!("Added enemy {}", level.enemies.len() - 1);
+        }
+        Command::MoveEnemy { x, y, index, to } => {
+            let mut level = load_tile(&dir, x, y)?;
+            let enemy = level
+                .enemies
+                .get_mut(index)
+                .ok_or_else(|| format!("no enemy {} in tile {}_{}", index, x, y))?;
+            enemy.location = point_arg(&to);
+            save_tile(&dir, &level)?;
+        }
+        Command::RemoveEnemy { x, y, index } => {
+            let mut level = load_tile(&dir, x, y)?;
+            if index >= level.enemies.len() {
+                return Err(format!("no enemy {} in tile {}_{}", index, x, y));
+            }
+            let removed = level.enemies.remove(index);
+            save_tile(&dir, &level)?;
+            println!("Removed {}", removed.description);
+        }
+        Command::List { x, y } => {
+            for (x, y) in selected_tiles(&dir, x, y)? {
+                print_level(&load_tile(&dir, x, y)?);
+            }
+        }
+        #[cfg(feature = "editor")]
+        Command::Edit { x, y } => {
+            let path = tile_path(&dir, x, y);
+            let level = if path.exists() {
+                load_tile(&dir, x, y)?
+            } else {
+                Level::new(x, y, "")
+            };
+            editor::run(path, level);
+        }
+        Command::Validate { x, y } => {
+            let mut failed = 0;
+            let mut levels = Vec::new();
+            for (x, y) in selected_tiles(&dir, x, y)? {
+                match load_tile(&dir, x, y) {
+                    Ok(level) => {
+                        println!("{}_{} ok", x, y);
+                        levels.push(level);
+                    }
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        failed += 1;
+                    }
+                }
+            }
+            // Scenery hanging over onto the next tile needs the tiles around it, so it's only
+            // checked when they are all being validated
+            if x.is_none() {
+                if let Err(err) = check_neighbours(&dir, &levels) {
+                    eprintln!("{}", err);
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                return Err(format!("{} tile(s) have problems", failed));
+            }
+        }
+        Command::Upgrade { x, y } => {
+            // Loading migrates the level, so saving it writes the current version
+            for (x, y) in selected_tiles(&dir, x, y)? {
+                let level = load_tile(&dir, x, y)?;
+                save_tile(&dir, &level)?;
+                println!("Rewrote {}_{} as version {}", x, y, CURRENT_VERSION);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn main() {
-    println!("Hello, world!");
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
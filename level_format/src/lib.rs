@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod migration;
+mod span;
+mod validation;
+
+pub use migration::{migrate, supported_versions, Migration, MIGRATIONS};
+pub use span::locate;
+pub use validation::{validate, validate_neighbours, Problem};
+
+// The schema version written into every level file
+pub const CURRENT_VERSION: &str = "0.1.0";
+
+// Every tile is one screen of the game
+pub const TILE_WIDTH: f32 = 1280.0;
+pub const TILE_HEIGHT: f32 = 720.0;
+
+// A single tile of the world, stored as `levels/x_y.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Level {
+    pub version: String,
+    pub description: String,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub objects: Vec<LevelObject>,
+    #[serde(default)]
+    pub enemies: Vec<LevelEnemy>,
+    #[serde(default)]
+    pub characters: Vec<LevelCharacter>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    #[serde(serialize_with = "serialize_number")]
+    pub x: f32,
+    #[serde(serialize_with = "serialize_number")]
+    pub y: f32,
+}
+
+// Hand written files use `500` rather than `500.0`, write whole numbers back the same way so
+// saving a level only changes what was edited
+fn serialize_number<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f32 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f32(*value)
+    }
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+// Scenery placed on the tile - location is the centre of the sprite, relative to the tile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelObject {
+    pub description: String,
+    pub location: Point,
+    pub hit_box: Point,
+    #[serde(serialize_with = "serialize_number")]
+    pub scale: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelEnemy {
+    pub description: String,
+    pub location: Point,
+    #[serde(default)]
+    pub route: Vec<Point>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LevelCharacter {
+    pub description: String,
+    pub location: Point,
+}
+
+// Scenery that has a sprite in `assets/images/objects`, with the hit box of that sprite at scale 1
+pub struct KnownObject {
+    pub description: &'static str,
+    pub hit_box: Point,
+}
+
+pub const KNOWN_OBJECTS: [KnownObject; 3] = [
+    KnownObject { description: "car", hit_box: Point { x: 450.0, y: 800.0 } },
+    KnownObject { description: "barbed_wire", hit_box: Point { x: 200.0, y: 50.0 } },
+    KnownObject { description: "blockade", hit_box: Point { x: 200.0, y: 50.0 } },
+];
+
+pub fn known_object(description: &str) -> Option<&'static KnownObject> {
+    KNOWN_OBJECTS.iter().find(|object| object.description == description)
+}
+
+// A problem found in a level file, pinned to where it is in the text
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    // Line and column, both starting at 1, when we know them
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, " {}", self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(PathBuf, std::io::Error),
+    Invalid(PathBuf, Vec<Diagnostic>),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            LevelError::Invalid(path, diagnostics) => {
+                // One problem per line in the usual file:line:column form so editors can jump to them
+                for (index, diagnostic) in diagnostics.iter().enumerate() {
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}:{}", path.display(), diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl Level {
+    pub fn new(x: i32, y: i32, description: &str) -> Self {
+        Level {
+            version: CURRENT_VERSION.to_string(),
+            description: description.to_string(),
+            x,
+            y,
+            objects: Vec::new(),
+            enemies: Vec::new(),
+            characters: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Level, LevelError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| LevelError::Io(path.to_path_buf(), err))?;
+        Level::from_json(&contents)
+            .map_err(|diagnostics| LevelError::Invalid(path.to_path_buf(), diagnostics))
+    }
+
+    // Parse, migrate and validate a level, reporting everything that is wrong with it
+    pub fn from_json(contents: &str) -> Result<Level, Vec<Diagnostic>> {
+        let parse_error = |err: serde_json::Error| {
+            // serde_json tacks the position onto the message, we report it separately
+            let message = err.to_string();
+            let suffix = format!(" at line {} column {}", err.line(), err.column());
+            vec![Diagnostic {
+                position: Some((err.line(), err.column())),
+                message: message.strip_suffix(&suffix).unwrap_or(&message).to_string(),
+            }]
+        };
+        let at = |pointer: &str, message: String| match locate(contents, pointer) {
+            Some(position) => Diagnostic { position: Some(position), message },
+            None => Diagnostic { position: None, message: format!("{}: {}", pointer, message) },
+        };
+
+        let mut value: Value = serde_json::from_str(contents).map_err(parse_error)?;
+        let level: Level = match value.get("version").and_then(|version| version.as_str()) {
+            // Parse the text directly when we can, so type errors keep their line and column
+            Some(CURRENT_VERSION) => serde_json::from_str(contents).map_err(parse_error)?,
+            _ => {
+                migrate(&mut value).map_err(|message| vec![at("/version", message)])?;
+                serde_json::from_value(value).map_err(parse_error)?
+            }
+        };
+
+        let problems = validate(&level);
+        if problems.is_empty() {
+            Ok(level)
+        } else {
+            Err(problems
+                .into_iter()
+                .map(|problem| at(&problem.pointer, problem.message))
+                .collect())
+        }
+    }
+
+    // Refuses to write out a level that wouldn't load again
+    pub fn save(&self, path: &Path) -> Result<(), LevelError> {
+        let problems = validate(self);
+        if !problems.is_empty() {
+            return Err(LevelError::Invalid(
+                path.to_path_buf(),
+                problems
+                    .into_iter()
+                    .map(|problem| Diagnostic {
+                        position: None,
+                        message: problem.to_string(),
+                    })
+                    .collect(),
+            ));
+        }
+
+        // Match the four space indent the hand written files use
+        let mut contents = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut contents, formatter);
+        self.serialize(&mut serializer)
+            .map_err(|err| LevelError::Io(path.to_path_buf(), err.into()))?;
+        contents.push(b'\n');
+        fs::write(path, contents)
+            .map_err(|err| LevelError::Io(path.to_path_buf(), err))
+    }
+
+    pub fn file_name(x: i32, y: i32) -> String {
+        format!("{}_{}.json", x, y)
+    }
+
+    // The tile coordinates of an `x_y.json` path, if it is a tile file
+    pub fn tile_coords(path: &Path) -> Option<(i32, i32)> {
+        if path.extension()?.to_str()? != "json" {
+            return None;
+        }
+        let (x, y) = path.file_stem()?.to_str()?.split_once('_')?;
+        Some((x.parse().ok()?, y.parse().ok()?))
+    }
+}
+
+// Tiles are validated one at a time as they load, this checks them against each other. Only
+// the first tile with problems is reported, the same as loading stops at the first bad tile.
+pub fn check_neighbours(dir: &Path, levels: &[Level]) -> Result<(), LevelError> {
+    let problems = validate_neighbours(levels);
+    let Some(&(tile, _)) = problems.first() else {
+        return Ok(());
+    };
+    Err(LevelError::Invalid(
+        dir.join(Level::file_name(tile.0, tile.1)),
+        problems
+            .into_iter()
+            .filter(|(problem_tile, _)| *problem_tile == tile)
+            .map(|(_, problem)| Diagnostic {
+                position: None,
+                message: problem.to_string(),
+            })
+            .collect(),
+    ))
+}
+
+// Every tile file in a directory, in a stable order
+pub fn find_tiles(dir: &Path) -> Result<Vec<(i32, i32)>, LevelError> {
+    let entries = fs::read_dir(dir).map_err(|err| LevelError::Io(dir.to_path_buf(), err))?;
+    let mut tiles: Vec<(i32, i32)> = entries
+        .flatten()
+        .filter_map(|entry| Level::tile_coords(&entry.path()))
+        .collect();
+    tiles.sort();
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Width and height from the header of a PNG file
+    fn png_size(path: &Path) -> Point {
+        let bytes = fs::read(path).unwrap();
+        let read = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as f32;
+        Point::new(read(16), read(20))
+    }
+
+    #[test]
+    fn known_objects_match_their_sprites() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/images/objects");
+        for object in KNOWN_OBJECTS.iter() {
+            let size = png_size(&dir.join(format!("{}.png", object.description)));
+            assert_eq!(object.hit_box, size, "{}", object.description);
+        }
+    }
+
+    #[test]
+    fn save_keeps_whole_numbers_whole() {
+        let mut level = Level::new(2, -1, "Saved");
+        level.objects.push(LevelObject {
+            description: "barbed_wire".to_string(),
+            location: Point::new(500.0, 362.5),
+            hit_box: Point::new(200.0, 50.0),
+            scale: 1.0,
+        });
+        let path = std::env::temp_dir().join(format!("level_format_save_{}.json", std::process::id()));
+        level.save(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.contains("\"x\": 500,"), "{}", contents);
+        assert!(contents.contains("\"y\": 362.5"), "{}", contents);
+        assert!(contents.contains("\"scale\": 1\n"), "{}", contents);
+        assert!(!contents.contains(".0,") && !contents.contains(".0\n"), "{}", contents);
+
+        let loaded = Level::from_json(&contents).unwrap();
+        assert_eq!(loaded.objects[0].location, Point::new(500.0, 362.5));
+        assert_eq!(loaded.objects[0].scale, 1.0);
+    }
+}
@@ -0,0 +1,114 @@
+use serde_json::Value;
+
+use crate::CURRENT_VERSION;
+
+// One step in bringing an old level file up to date. Steps run in order, each one
+// rewriting the raw JSON from `from` to `to`, until the file reaches CURRENT_VERSION.
+// When the schema changes, bump CURRENT_VERSION and add the step that gets there here.
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub apply: fn(&mut Value),
+}
+
+pub const MIGRATIONS: [Migration; 0] = [];
+
+// Every version we can read, oldest first
+pub fn supported_versions() -> Vec<&'static str> {
+    versions_from(&MIGRATIONS)
+}
+
+fn versions_from(migrations: &[Migration]) -> Vec<&'static str> {
+    let mut versions: Vec<&'static str> = migrations.iter().map(|migration| migration.from).collect();
+    versions.push(CURRENT_VERSION);
+    versions
+}
+
+// Bring a level up to the current version, or explain why we can't
+pub fn migrate(value: &mut Value) -> Result<(), String> {
+    migrate_with(value, &MIGRATIONS)
+}
+
+fn migrate_with(value: &mut Value, migrations: &[Migration]) -> Result<(), String> {
+    loop {
+        let version = match value.get("version").and_then(|version| version.as_str()) {
+            Some(version) => version.to_string(),
+            None => return Err("missing the \"version\" field".to_string()),
+        };
+        if version == CURRENT_VERSION {
+            return Ok(());
+        }
+
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or_else(|| {
+                format!(
+                    "unsupported version \"{}\", expected one of {}",
+                    version,
+                    versions_from(migrations).join(", ")
+                )
+            })?;
+        (migration.apply)(value);
+        value["version"] = Value::String(migration.to.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Two made up steps ending at the current version, standing in for real ones
+    fn rename_name(value: &mut Value) {
+        let name = value["name"].take();
+        value["description"] = name;
+        value.as_object_mut().unwrap().remove("name");
+    }
+
+    fn add_characters(value: &mut Value) {
+        value["characters"] = json!([]);
+    }
+
+    const STEPS: [Migration; 2] = [
+        Migration { from: "0.0.1", to: "0.0.2", apply: rename_name },
+        Migration { from: "0.0.2", to: CURRENT_VERSION, apply: add_characters },
+    ];
+
+    #[test]
+    fn runs_every_step_in_order() {
+        let mut value = json!({"version": "0.0.1", "name": "Old tile"});
+        migrate_with(&mut value, &STEPS).unwrap();
+        assert_eq!(value, json!({"version": CURRENT_VERSION, "description": "Old tile", "characters": []}));
+    }
+
+    #[test]
+    fn starts_part_way_through() {
+        let mut value = json!({"version": "0.0.2", "description": "Newer tile"});
+        migrate_with(&mut value, &STEPS).unwrap();
+        assert_eq!(value, json!({"version": CURRENT_VERSION, "description": "Newer tile", "characters": []}));
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let mut value = json!({"version": CURRENT_VERSION, "name": "Untouched"});
+        migrate_with(&mut value, &STEPS).unwrap();
+        assert_eq!(value, json!({"version": CURRENT_VERSION, "name": "Untouched"}));
+    }
+
+    #[test]
+    fn unknown_version_lists_the_supported_ones() {
+        let mut value = json!({"version": "9.9.9"});
+        let err = migrate_with(&mut value, &STEPS).unwrap_err();
+        assert_eq!(
+            err,
+            format!("unsupported version \"9.9.9\", expected one of 0.0.1, 0.0.2, {}", CURRENT_VERSION)
+        );
+    }
+
+    #[test]
+    fn missing_version() {
+        let err = migrate_with(&mut json!({"x": 0}), &STEPS).unwrap_err();
+        assert_eq!(err, "missing the \"version\" field");
+    }
+}
@@ -0,0 +1,176 @@
+// Finds where a value sits in the original JSON text so problems can point at a line and column.
+// serde_json throws this information away once parsing succeeds, so we walk the text ourselves.
+// Only ever run over text that has already parsed, so malformed input just gives up.
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if byte & 0xC0 != 0x80 {
+            // Count characters rather than the bytes of multi-byte characters
+            self.column += 1;
+        }
+        Some(byte)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.bump();
+        let start = self.pos;
+        loop {
+            match self.bump()? {
+                b'\\' => {
+                    self.bump()?;
+                }
+                b'"' => break,
+                _ => {}
+            }
+        }
+        String::from_utf8(self.bytes[start..self.pos - 1].to_vec()).ok()
+    }
+
+    fn value(&mut self, path: &mut Vec<String>, target: &[&str]) -> Option<(usize, usize)> {
+        self.skip_whitespace();
+        if path.iter().map(|segment| segment.as_str()).eq(target.iter().copied()) {
+            return Some((self.line, self.column));
+        }
+
+        match self.peek()? {
+            b'{' => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == b'}' {
+                        self.bump();
+                        return None;
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.bump();
+                    path.push(key);
+                    if let Some(found) = self.value(path, target) {
+                        return Some(found);
+                    }
+                    path.pop();
+                    self.skip_whitespace();
+                    if self.peek()? == b',' {
+                        self.bump();
+                    }
+                }
+            }
+            b'[' => {
+                self.bump();
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == b']' {
+                        self.bump();
+                        return None;
+                    }
+                    path.push(index.to_string());
+                    if let Some(found) = self.value(path, target) {
+                        return Some(found);
+                    }
+                    path.pop();
+                    index += 1;
+                    self.skip_whitespace();
+                    if self.peek()? == b',' {
+                        self.bump();
+                    }
+                }
+            }
+            b'"' => {
+                self.string()?;
+                None
+            }
+            _ => {
+                while !matches!(self.peek()?, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r') {
+                    self.bump();
+                }
+                None
+            }
+        }
+    }
+}
+
+// Line and column (both starting at 1) of the value at a JSON pointer such as `/objects/0/hit_box`
+pub fn locate(source: &str, pointer: &str) -> Option<(usize, usize)> {
+    let target: Vec<&str> = pointer.split('/').skip(1).collect();
+    let mut scanner = Scanner {
+        bytes: source.as_bytes(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    scanner.value(&mut Vec::new(), &target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"{
+    "name": "café \"corner\"",
+    "note": "naïve",
+    "objects": [
+        {"x": 1, "y": [2, 3]},
+        {"x": 4}
+    ]
+}"#;
+
+    #[test]
+    fn finds_object_fields() {
+        assert_eq!(locate(SOURCE, "/name"), Some((2, 13)));
+        assert_eq!(locate(SOURCE, "/objects"), Some((4, 16)));
+    }
+
+    #[test]
+    fn finds_array_entries() {
+        assert_eq!(locate(SOURCE, "/objects/0"), Some((5, 9)));
+        assert_eq!(locate(SOURCE, "/objects/1/x"), Some((6, 15)));
+        assert_eq!(locate(SOURCE, "/objects/0/y/1"), Some((5, 27)));
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        let source = "{\"é\": \"ü\", \"b\": 1}";
+        assert_eq!(locate(source, "/b"), Some((1, 17)));
+    }
+
+    #[test]
+    fn skips_escaped_quotes() {
+        // The escaped quotes in "name" mustn't end the string early
+        assert_eq!(locate(SOURCE, "/note"), Some((3, 13)));
+    }
+
+    #[test]
+    fn missing_values() {
+        assert_eq!(locate(SOURCE, "/objects/2"), None);
+        assert_eq!(locate(SOURCE, "/colour"), None);
+        assert_eq!(locate("{\"broken\": ", "/other"), None);
+    }
+
+    #[test]
+    fn whole_document() {
+        assert_eq!(locate("  [1]", ""), Some((1, 3)));
+    }
+}
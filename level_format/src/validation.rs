@@ -0,0 +1,326 @@
+use std::fmt;
+
+use crate::{known_object, Level, LevelObject, Point, CURRENT_VERSION, TILE_HEIGHT, TILE_WIDTH};
+
+// Something wrong with a level, and the JSON pointer to the value at fault
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub pointer: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.pointer, self.message)
+    }
+}
+
+fn inside_tile(point: Point) -> bool {
+    point.x >= 0.0 && point.x <= TILE_WIDTH && point.y >= 0.0 && point.y <= TILE_HEIGHT
+}
+
+fn overlaps(a: &LevelObject, b: &LevelObject) -> bool {
+    let reach_x = (a.hit_box.x * a.scale + b.hit_box.x * b.scale) / 2.0;
+    let reach_y = (a.hit_box.y * a.scale + b.hit_box.y * b.scale) / 2.0;
+    (a.location.x - b.location.x).abs() < reach_x && (a.location.y - b.location.y).abs() < reach_y
+}
+
+pub fn validate(level: &Level) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut problem = |pointer: String, message: String| problems.push(Problem { pointer, message });
+
+    if level.version != CURRENT_VERSION {
+        problem(
+            "/version".to_string(),
+            format!("version \"{}\" needs upgrading to {}", level.version, CURRENT_VERSION),
+        );
+    }
+
+    for (index, object) in level.objects.iter().enumerate() {
+        let pointer = format!("/objects/{}", index);
+
+        if known_object(&object.description).is_none() {
+            problem(
+                format!("{}/description", pointer),
+                format!("unknown object \"{}\", there is no images/objects/{}.png", object.description, object.description),
+            );
+        }
+        if object.hit_box.x <= 0.0 || object.hit_box.y <= 0.0 {
+            problem(
+                format!("{}/hit_box", pointer),
+                format!("hit box {} must be wider and taller than zero", object.hit_box),
+            );
+        }
+        if object.scale <= 0.0 {
+            problem(format!("{}/scale", pointer), format!("scale {} must be above zero", object.scale));
+        }
+        // Scenery can hang over the edge, what it covers on the next tile is checked by validate_neighbours
+        if !inside_tile(object.location) {
+            problem(
+                format!("{}/location", pointer),
+                format!("{} is outside the {}x{} tile", object.location, TILE_WIDTH, TILE_HEIGHT),
+            );
+        }
+
+        // Blockers sitting on top of each other leave dead space nothing can reach
+        for (other_index, other) in level.objects.iter().enumerate().take(index) {
+            if overlaps(object, other) {
+                problem(
+                    pointer.clone(),
+                    format!("{} overlaps {} (object {})", object.description, other.description, other_index),
+                );
+            }
+        }
+    }
+
+    for (index, enemy) in level.enemies.iter().enumerate() {
+        let pointer = format!("/enemies/{}", index);
+        if !inside_tile(enemy.location) {
+            problem(
+                format!("{}/location", pointer),
+                format!("{} is outside the {}x{} tile", enemy.location, TILE_WIDTH, TILE_HEIGHT),
+            );
+        }
+        for (point_index, point) in enemy.route.iter().enumerate() {
+            if !inside_tile(*point) {
+                problem(
+                    format!("{}/route/{}", pointer, point_index),
+                    format!("{} is outside the {}x{} tile", point, TILE_WIDTH, TILE_HEIGHT),
+                );
+            }
+        }
+    }
+
+    for (index, character) in level.characters.iter().enumerate() {
+        if !inside_tile(character.location) {
+            problem(
+                format!("/characters/{}/location", index),
+                format!("{} is outside the {}x{} tile", character.location, TILE_WIDTH, TILE_HEIGHT),
+            );
+        }
+    }
+
+    problems
+}
+
+// A tile can't see what is around it, so scenery hanging over its edge is checked here once every
+// tile is loaded. It may cover the next tile, but not the scenery already placed there. Past the
+// edge of the world there is nothing to get in the way of.
+pub fn validate_neighbours(levels: &[Level]) -> Vec<((i32, i32), Problem)> {
+    let mut problems = Vec::new();
+
+    for level in levels {
+        for other in levels {
+            let (dx, dy) = (other.x - level.x, other.y - level.y);
+            if (dx, dy) == (0, 0) || dx.abs() > 1 || dy.abs() > 1 {
+                continue;
+            }
+
+            for (index, object) in level.objects.iter().enumerate() {
+                for (other_index, other_object) in other.objects.iter().enumerate() {
+                    // Put the other tile's scenery where it is from this tile
+                    let mut moved = other_object.clone();
+                    moved.location.x += dx as f32 * TILE_WIDTH;
+                    moved.location.y += dy as f32 * TILE_HEIGHT;
+                    // Only reported from one side
+                    if overlaps(object, &moved) && (level.x, level.y) < (other.x, other.y) {
+                        problems.push((
+                            (level.x, level.y),
+                            Problem {
+                                pointer: format!("/objects/{}", index),
+                                message: format!(
+                                    "{} overlaps {} (object {}) on tile {}_{}",
+                                    object.description, other_object.description, other_index, other.x, other.y
+                                ),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_neighbours;
+    use crate::{Level, LevelObject, Point};
+
+    const TILE: &str = r#"{
+    "version": "0.1.0",
+    "description": "Test tile",
+    "x": 0,
+    "y": 0,
+    "objects": [{
+        "description": "barbed_wire",
+        "location": {"x": 200, "y": 650},
+        "hit_box": {"x": 200, "y": 50},
+        "scale": 1
+    },{
+        "description": "blockade",
+        "location": {"x": 600, "y": 100},
+        "hit_box": {"x": 200, "y": 50},
+        "scale": 1
+    }],
+    "enemies": [{
+        "description": "zombie",
+        "location": {"x": 150, "y": 300},
+        "route": [{"x": 150, "y": 550}]
+    }]
+}"#;
+
+    // The test tile with one piece of it swapped out, and everything that's wrong with the result
+    fn diagnostics(from: &str, to: &str) -> Vec<String> {
+        assert!(TILE.contains(from), "{}", from);
+        match Level::from_json(&TILE.replacen(from, to, 1)) {
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_tile() {
+        let level = Level::from_json(TILE).unwrap();
+        assert_eq!(level.objects.len(), 2);
+        assert_eq!(level.enemies[0].route.len(), 1);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert_eq!(
+            diagnostics(r#""0.1.0""#, r#""0.0.9""#),
+            vec![r#"2:16: unsupported version "0.0.9", expected one of 0.1.0"#]
+        );
+    }
+
+    #[test]
+    fn missing_version() {
+        // Nothing to point at, so the pointer goes in the message instead
+        assert_eq!(
+            diagnostics("    \"version\": \"0.1.0\",\n", ""),
+            vec![r#" /version: missing the "version" field"#]
+        );
+    }
+
+    #[test]
+    fn type_error() {
+        assert_eq!(
+            diagnostics(r#""x": 0,"#, r#""x": "zero","#),
+            vec![r#"4:15: invalid type: string "zero", expected i32"#]
+        );
+    }
+
+    #[test]
+    fn unknown_object() {
+        assert_eq!(
+            diagnostics(r#""blockade""#, r#""tree""#),
+            vec![r#"12:24: unknown object "tree", there is no images/objects/tree.png"#]
+        );
+    }
+
+    #[test]
+    fn empty_hit_box() {
+        assert_eq!(
+            diagnostics(r#""hit_box": {"x": 200, "y": 50}"#, r#""hit_box": {"x": 0, "y": 50}"#),
+            vec!["9:20: hit box (0, 50) must be wider and taller than zero"]
+        );
+    }
+
+    #[test]
+    fn bad_scale() {
+        assert_eq!(
+            diagnostics(r#""scale": 1"#, r#""scale": 0"#),
+            vec!["10:18: scale 0 must be above zero"]
+        );
+    }
+
+    #[test]
+    fn object_outside_tile() {
+        assert_eq!(
+            diagnostics(r#"{"x": 200, "y": 650}"#, r#"{"x": 1400, "y": 650}"#),
+            vec!["8:21: (1400, 650) is outside the 1280x720 tile"]
+        );
+    }
+
+    #[test]
+    fn object_hanging_over_the_edge() {
+        // The centre is on the tile, the rest is up to the tiles around it
+        assert_eq!(diagnostics(r#"{"x": 200, "y": 650}"#, r#"{"x": 0, "y": 650}"#), Vec::<String>::new());
+        assert_eq!(diagnostics(r#"{"x": 600, "y": 100}"#, r#"{"x": 600, "y": 720}"#), Vec::<String>::new());
+    }
+
+    fn tile(x: i32, y: i32, objects: &[(&str, f32, f32)]) -> Level {
+        let mut level = Level::new(x, y, "Neighbour");
+        for &(description, location_x, location_y) in objects {
+            level.objects.push(LevelObject {
+                description: description.to_string(),
+                location: Point::new(location_x, location_y),
+                hit_box: Point::new(200.0, 50.0),
+                scale: 1.0,
+            });
+        }
+        level
+    }
+
+    fn neighbour_problems(levels: &[Level]) -> Vec<String> {
+        validate_neighbours(levels)
+            .into_iter()
+            .map(|((x, y), problem)| format!("{}_{}{}", x, y, problem))
+            .collect()
+    }
+
+    #[test]
+    fn overhanging_onto_an_empty_neighbour() {
+        let levels = [tile(0, 0, &[("barbed_wire", 1250.0, 300.0)]), tile(1, 0, &[("blockade", 200.0, 300.0)])];
+        assert_eq!(neighbour_problems(&levels), Vec::<String>::new());
+    }
+
+    #[test]
+    fn overhanging_onto_neighbouring_scenery() {
+        // Both hang over the shared edge into each other
+        let levels = [tile(1, 0, &[("blockade", 50.0, 300.0)]), tile(0, 0, &[("barbed_wire", 1250.0, 310.0)])];
+        assert_eq!(
+            neighbour_problems(&levels),
+            vec!["0_0/objects/0: barbed_wire overlaps blockade (object 0) on tile 1_0"]
+        );
+
+        // Diagonal neighbours count too
+        let levels = [tile(0, 0, &[("barbed_wire", 1250.0, 710.0)]), tile(1, 1, &[("blockade", 20.0, 5.0)])];
+        assert_eq!(
+            neighbour_problems(&levels),
+            vec!["0_0/objects/0: barbed_wire overlaps blockade (object 0) on tile 1_1"]
+        );
+    }
+
+    #[test]
+    fn enemy_outside_tile() {
+        assert_eq!(
+            diagnostics(r#"[{"x": 150, "y": 550}]"#, r#"[{"x": 150, "y": 550}, {"x": -10, "y": 550}]"#),
+            vec!["20:41: (-10, 550) is outside the 1280x720 tile"]
+        );
+    }
+
+    #[test]
+    fn overlapping_objects() {
+        assert_eq!(
+            diagnostics(r#"{"x": 600, "y": 100}"#, r#"{"x": 250, "y": 640}"#),
+            vec!["11:7: blockade overlaps barbed_wire (object 0)"]
+        );
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let source = TILE.replacen(r#""blockade""#, r#""tree""#, 1).replacen(r#""scale": 1"#, r#""scale": -1"#, 1);
+        let found: Vec<String> = Level::from_json(&source)
+            .unwrap_err()
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            vec!["10:18: scale -1 must be above zero", r#"12:24: unknown object "tree", there is no images/objects/tree.png"#]
+        );
+    }
+}
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use std::path::Path;
 
 use crate::game::OnGameScreen;
-use crate::{GAME_WIDTH, GAME_HEIGHT,GameDetails};
+use crate::interpolation::Interpolated;
 
 const BLOOD_TTL: f32 = 3.0;
 const BLOOD_TTM: f32 = 0.2;
@@ -18,7 +18,6 @@ pub struct Blood {
 
 pub fn add_blood_spatter(
     commands: &mut Commands,
-    game_details: &Res<GameDetails>,
     asset_server: &AssetServer,
     loc: Vec2,
     rot: f32,
@@ -29,14 +28,11 @@ pub fn add_blood_spatter(
     commands.spawn((
         SpriteBundle {
             texture: texture_handle,
-            transform: Transform::from_xyz(
-                loc.x - game_details.offset_x - (GAME_WIDTH/2.0), 
-                loc.y - game_details.offset_y - (GAME_HEIGHT/2.0), 
-                1.0
-            ).with_scale(Vec3::splat(3.0)),
+            transform: Transform::from_xyz(loc.x, loc.y, 1.0).with_scale(Vec3::splat(3.0)),
             ..default()
         },
     )) 
+    .insert(Interpolated::new(loc))
     .insert(Blood{
         ttl: BLOOD_TTL,
         ttm: BLOOD_TTM,
@@ -48,21 +44,20 @@ pub fn add_blood_spatter(
 
 pub fn update_blood_spatter(
     mut commands: Commands, 
-    mut bloods: Query<(Entity, &mut Blood, &mut Transform),>,
-    time: Res<Time>,
-    game_details: Res<GameDetails>
+    mut bloods: Query<(Entity, &mut Blood, &mut Interpolated),>,
+    time: Res<FixedTime>,
 ){
-    for (entity, mut blood, mut transform) in bloods.iter_mut() {
+    for (entity, mut blood, mut interpolated) in bloods.iter_mut() {
         if blood.ttm >= 0.0 {
             // Move the blood
-            blood.ttm -= time.delta_seconds();
+            blood.ttm -= time.period.as_secs_f32();
 
-            blood.loc.x += blood.rot.sin() * BLOOD_SPATTER_SPEED * time.delta_seconds();
-            blood.loc.y += blood.rot.sin() * BLOOD_SPATTER_SPEED * time.delta_seconds();
+            blood.loc.x += blood.rot.sin() * BLOOD_SPATTER_SPEED * time.period.as_secs_f32();
+            blood.loc.y += blood.rot.sin() * BLOOD_SPATTER_SPEED * time.period.as_secs_f32();
             
         }else{
             // Decay the blood
-            blood.ttl -= time.delta_seconds();
+            blood.ttl -= time.period.as_secs_f32();
             // println!("blood ttl: {:?}", blood.ttl);
     
             if blood.ttl <= 0.0 {
@@ -70,8 +65,6 @@ pub fn update_blood_spatter(
             }
         }
 
-        // Apply any offset
-        transform.translation.x = blood.loc.x - game_details.offset_x - (GAME_WIDTH/2.0);
-        transform.translation.y = blood.loc.y - game_details.offset_y - (GAME_HEIGHT/2.0);
+        interpolated.current = blood.loc;
     }
 }
\ No newline at end of file
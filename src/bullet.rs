@@ -1,77 +1,225 @@
 use bevy::prelude::*;
 use bevy::sprite::collide_aabb::collide;
 use rand::Rng; 
+use std::path::Path;
 
-use crate::{GAME_WIDTH,GAME_HEIGHT,GameDetails};
+use crate::GameDetails;
 use crate::zombie::Zombie;
 use crate::blood;
+use crate::game::RunStats;
+use crate::settings::Settings;
+use crate::sound::{PlaySound, Sound};
+use crate::hud::ZombieKilled;
+use crate::game::OnGameScreen;
+use crate::weapon::Weapon;
+use crate::rng::GameRng;
+use crate::interpolation::Interpolated;
+
+pub const BULLET_HIT_BOX: Vec2 = Vec2::new(10.0, 20.0);
 
 #[derive(Component)]
 pub struct Bullet {
     pub loc: Vec2,
     pub angle: f32,
     pub hit_box: Vec2,
-    pub damage: i32
+    pub damage: i32,
+    pub speed: f32,
+}
+
+impl Bullet {
+    pub fn new(loc: Vec2, angle: f32, weapon: &Weapon) -> Self {
+        Bullet {
+            loc: loc,
+            angle: angle,
+            hit_box: BULLET_HIT_BOX,
+            damage: weapon.damage,
+            speed: weapon.bullet_speed,
+        }
+    }
+}
+
+pub fn bullet_texture(asset_server: &Res<AssetServer>) -> Handle<Image> {
+    let texture_path = Path::new("images").join("objects").join("bullet.png");
+    asset_server.load(texture_path)
 }
 
-const BULLET_SPEED: f32 = 500.0;
+pub fn spawn_bullet(
+    commands: &mut Commands,
+    texture_handle: Handle<Image>,
+    bullet: Bullet,
+){
+    commands.spawn((
+            SpriteBundle {
+                texture: texture_handle,
+                transform: Transform::from_xyz(bullet.loc.x, bullet.loc.y, 2.0)
+                    .with_rotation(Quat::from_rotation_z(bullet.angle)),
+                ..default()
+            },
+        ))
+        .insert(Interpolated::new(bullet.loc))
+        .insert(bullet)
+        .insert(OnGameScreen);
+}
 
 pub fn bullet_mover(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<FixedTime>,
     mut bullets: Query<(
         Entity,
         &mut Bullet,
         &mut Transform,
+        &mut Interpolated,
     )>,
     game_details: Res<GameDetails>
 ){
-    for (entity, mut bullet, mut transform) in bullets.iter_mut(){
+    for (entity, mut bullet, mut transform, mut interpolated) in bullets.iter_mut(){
         transform.rotation = Quat::from_rotation_z(bullet.angle);
-        bullet.loc.x += bullet.angle.sin() * (BULLET_SPEED * time.delta_seconds());
-        bullet.loc.y -= bullet.angle.cos() * (BULLET_SPEED * time.delta_seconds());
-        transform.translation.x = bullet.loc.x - game_details.offset_x - (GAME_WIDTH/2.0);
-        transform.translation.y = bullet.loc.y - game_details.offset_y - (GAME_HEIGHT/2.0);
+        bullet.loc.x += bullet.angle.sin() * (bullet.speed * time.period.as_secs_f32());
+        bullet.loc.y -= bullet.angle.cos() * (bullet.speed * time.period.as_secs_f32());
+        interpolated.current = bullet.loc;
 
         // Catch-all to make sure bullet doesn't live forever, but it should hit an object, ideally
-        if bullet.loc.x < 0.0 || bullet.loc.x >= GAME_WIDTH * game_details.width as f32 
-            || bullet.loc.y < 0.0 || bullet.loc.y >= GAME_HEIGHT * game_details.height as f32 {
+        if !game_details.bounds.contains(bullet.loc) {
             commands.entity(entity).despawn();
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn bullet_collision(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    bullets: Query<(Entity, &Bullet, &Transform,), (With<Bullet>, Without<Zombie>)>,
-    mut zombies: Query<(Entity, &mut Zombie, &mut Transform), (With<Zombie>, Without<Bullet>)>,
-    game_details: Res<GameDetails>
+    bullets: Query<(Entity, &Bullet)>,
+    mut zombies: Query<(Entity, &mut Zombie)>,
+    mut stats: ResMut<RunStats>,
+    settings: Res<Settings>,
+    mut sounds: EventWriter<PlaySound>,
+    mut killed: EventWriter<ZombieKilled>,
+    mut game_rng: ResMut<GameRng>,
 ){
-    let mut rng = rand::thread_rng();
-
-    for (bullet_entity, bullet, bullet_transform) in bullets.iter() {
-        for (zombie_entity, mut zombie, zombie_transform) in zombies.iter_mut() {
-            if collide(bullet_transform.translation, bullet.hit_box, zombie_transform.translation, zombie.hit_box).is_some() {
-                let cur_pos = Vec2::new(zombie.pos.x,zombie.pos.y);
-                
-                for _ in 0..rng.gen_range(2..4){
-                    let angle_diff = rng.gen_range(-std::f32::consts::PI/3.0..std::f32::consts::PI/3.0);
-                    blood::add_blood_spatter(
-                        &mut commands,
-                        &game_details,
-                        &asset_server,
-                        cur_pos,
-                        bullet.angle - (std::f32::consts::PI/2.0) + angle_diff
-                    );
-                }
-                
-                zombie.health -= bullet.damage;
-                commands.entity(bullet_entity).despawn();
-                if zombie.health <= 0 {
-                    commands.entity(zombie_entity).despawn();
-                }
+    let rng = game_rng.cosmetic();
+
+    // Goes by where things are in the game rather than where they are drawn, which lags behind
+    for (bullet_entity, bullet) in bullets.iter() {
+        for (zombie_entity, mut zombie) in zombies.iter_mut() {
+            // Killed by an earlier bullet this tick and waiting to be despawned, so this one
+            // carries on to whatever is behind it
+            if zombie.health <= 0 {
+                continue;
+            }
+            if collide(bullet.loc.extend(0.0), bullet.hit_box, zombie.pos.extend(0.0), zombie.hit_box).is_none() {
+                continue;
             }
+            let cur_pos = Vec2::new(zombie.pos.x,zombie.pos.y);
+            
+            let spatters = if settings.blood { rng.gen_range(2..4) } else { 0 };
+            for _ in 0..spatters {
+                let angle_diff = rng.gen_range(-std::f32::consts::PI/3.0..std::f32::consts::PI/3.0);
+                blood::add_blood_spatter(
+                    &mut commands,
+                    &asset_server,
+                    cur_pos,
+                    bullet.angle - (std::f32::consts::PI/2.0) + angle_diff
+                );
+            }
+            
+            zombie.health -= bullet.damage;
+            commands.entity(bullet_entity).despawn();
+            stats.shots_hit += 1;
+            if zombie.health <= 0 {
+                commands.entity(zombie_entity).despawn();
+                stats.zombies_killed += 1;
+                killed.send(ZombieKilled { at: cur_pos });
+                sounds.send(PlaySound::at(Sound::ZombieDeath, cur_pos));
+            } else {
+                sounds.send(PlaySound::at(Sound::Hit, cur_pos));
+            }
+            // A bullet only ever hits one zombie
+            break;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .add_event::<PlaySound>()
+            .add_event::<ZombieKilled>()
+            .insert_resource(Settings {
+                blood: false,
+                ..default()
+            })
+            .insert_resource(GameRng::new(Some(1)))
+            .insert_resource(RunStats::default())
+            .add_systems(Update, bullet_collision);
+        app
+    }
+
+    fn spawn_zombie(app: &mut App, pos: Vec2, health: i32) -> Entity {
+        let mut zombie = Zombie::new(vec![pos], 0);
+        zombie.health = health;
+        app.world.spawn(zombie).id()
+    }
+
+    fn spawn_bullet(app: &mut App, loc: Vec2) -> Entity {
+        app.world.spawn(Bullet {
+            loc: loc,
+            angle: 0.0,
+            hit_box: BULLET_HIT_BOX,
+            damage: 1,
+            speed: 0.0,
+        }).id()
+    }
+
+    #[test]
+    fn pellet_misses_a_zombie_killed_the_same_tick() {
+        let mut app = app();
+        let zombie = spawn_zombie(&mut app, Vec2::ZERO, 1);
+        let first = spawn_bullet(&mut app, Vec2::ZERO);
+        let second = spawn_bullet(&mut app, Vec2::new(5.0, 0.0));
+        app.update();
+
+        assert!(app.world.get_entity(zombie).is_none());
+        // Only one of them is spent on the kill, the other flies on
+        assert_eq!(
+            [first, second].iter().filter(|bullet| app.world.get_entity(**bullet).is_some()).count(),
+            1
+        );
+        let stats = app.world.resource::<RunStats>();
+        assert_eq!(stats.zombies_killed, 1);
+        assert_eq!(stats.shots_hit, 1);
+        assert_eq!(app.world.resource::<Events<PlaySound>>().len(), 1);
+    }
+
+    #[test]
+    fn pellet_carries_on_to_the_zombie_behind() {
+        let mut app = app();
+        let front = spawn_zombie(&mut app, Vec2::ZERO, 1);
+        let behind = spawn_zombie(&mut app, Vec2::new(0.0, 40.0), 1);
+        spawn_bullet(&mut app, Vec2::new(0.0, 20.0));
+        spawn_bullet(&mut app, Vec2::new(0.0, 20.0));
+        app.update();
+
+        assert!(app.world.get_entity(front).is_none());
+        assert!(app.world.get_entity(behind).is_none());
+        let stats = app.world.resource::<RunStats>();
+        assert_eq!(stats.zombies_killed, 2);
+        assert_eq!(stats.shots_hit, 2);
+    }
+
+    #[test]
+    fn bullet_hits_one_zombie() {
+        let mut app = app();
+        let first = spawn_zombie(&mut app, Vec2::ZERO, 5);
+        let second = spawn_zombie(&mut app, Vec2::new(0.0, 40.0), 5);
+        spawn_bullet(&mut app, Vec2::new(0.0, 20.0));
+        app.update();
+
+        let health = |zombie: Entity| app.world.get::<Zombie>(zombie).unwrap().health;
+        assert_eq!(health(first) + health(second), 9);
+        assert_eq!(app.world.resource::<RunStats>().shots_hit, 1);
+    }
+}
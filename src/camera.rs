@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+
+use crate::{GAME_WIDTH, GAME_HEIGHT, GameDetails};
+use crate::game::OnGameScreen;
+use crate::player::Player;
+
+// The camera that follows the player around the world
+#[derive(Component)]
+pub struct GameCamera;
+
+pub fn spawn_game_camera(commands: &mut Commands) {
+    // Always show at least a whole screen of the world, scaling to fit whatever size the window is
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::AutoMin {
+        min_width: GAME_WIDTH,
+        min_height: GAME_HEIGHT,
+    };
+
+    commands
+        .spawn(camera)
+        .insert(GameCamera)
+        .insert(OnGameScreen);
+}
+
+// Keep the player in the middle of the screen, except near the edges of the world where
+// the camera stops so we never look past the last tile. Follows where the player is drawn
+// rather than where they are in the game, or the player would jitter against the world.
+pub fn camera_follow(
+    game_details: Res<GameDetails>,
+    players: Query<&Transform, (With<Player>, Without<GameCamera>)>,
+    mut cameras: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+){
+    if players.is_empty() || cameras.is_empty() {
+        return;
+    }
+    let player = players.single();
+    let (mut transform, projection) = cameras.single_mut();
+
+    let half_view = projection.area.half_size();
+    let bounds = game_details.bounds;
+    let mut target = player.translation.truncate();
+    for axis in 0..2 {
+        let min = bounds.min[axis] + half_view[axis];
+        let max = bounds.max[axis] - half_view[axis];
+        // A world smaller than the screen just sits in the middle
+        target[axis] = if min > max {
+            (bounds.min[axis] + bounds.max[axis]) / 2.0
+        } else {
+            target[axis].clamp(min, max)
+        };
+    }
+
+    transform.translation.x = target.x;
+    transform.translation.y = target.y;
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+
+// Static scenery that blocks movement, positioned in world coordinates (centre of the hit box)
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CollisionObject {
+    pub loc: Vec2,
+    pub hit_box: Vec2,
+}
+
+impl CollisionObject {
+    pub fn overlaps(&self, loc: Vec2, hit_box: Vec2) -> bool {
+        let distance = (self.loc - loc).abs();
+        let reach = (self.hit_box + hit_box) / 2.0;
+        distance.x < reach.x && distance.y < reach.y
+    }
+
+    // How much of a hit box at `loc` is inside this object, zero when they don't overlap
+    pub fn overlap_area(&self, loc: Vec2, hit_box: Vec2) -> f32 {
+        let depth = ((self.hit_box + hit_box) / 2.0 - (self.loc - loc).abs()).max(Vec2::ZERO);
+        depth.x * depth.y
+    }
+
+    // Does the line from start to end pass through this object (slab test against the hit box)
+    pub fn blocks_line(&self, start: Vec2, end: Vec2) -> bool {
+        let min = self.loc - self.hit_box / 2.0;
+        let max = self.loc + self.hit_box / 2.0;
+        let direction = end - start;
+
+        let mut t_min: f32 = 0.0;
+        let mut t_max: f32 = 1.0;
+        for axis in 0..2 {
+            if direction[axis].abs() < f32::EPSILON {
+                // Parallel to this axis, so it has to start between the slabs
+                if start[axis] < min[axis] || start[axis] > max[axis] {
+                    return false;
+                }
+            } else {
+                let t1 = (min[axis] - start[axis]) / direction[axis];
+                let t2 = (max[axis] - start[axis]) / direction[axis];
+                t_min = t_min.max(t1.min(t2));
+                t_max = t_max.min(t1.max(t2));
+                if t_min > t_max {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,509 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::utils::{config_dir, read_json, write_json};
+
+// Sticks have to be pushed this far before they count, so a worn stick doesn't drift
+const STICK_DEADZONE: f32 = 0.2;
+
+// Everything the player can ask for, whichever key or button it is bound to
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Reload,
+    NextWeapon,
+    PreviousWeapon,
+    Weapon1,
+    Weapon2,
+    Weapon3,
+    UseMedkit,
+    Pause,
+}
+
+impl Action {
+    // In the order they are listed on the controls screen
+    pub const ALL: [Action; 13] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+        Action::Reload,
+        Action::NextWeapon,
+        Action::PreviousWeapon,
+        Action::Weapon1,
+        Action::Weapon2,
+        Action::Weapon3,
+        Action::UseMedkit,
+        Action::Pause,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Fire => "Fire",
+            Action::Reload => "Reload",
+            Action::NextWeapon => "Next weapon",
+            Action::PreviousWeapon => "Previous weapon",
+            Action::Weapon1 => "Weapon 1",
+            Action::Weapon2 => "Weapon 2",
+            Action::Weapon3 => "Weapon 3",
+            Action::UseMedkit => "Use medkit",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+// A keyboard or mouse input an action can be bound to
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => {
+                let name = format!("{:?}", key);
+                // Key1 reads better as 1
+                match name.strip_prefix("Key") {
+                    Some(digit) if !digit.is_empty() => digit.to_string(),
+                    _ => name,
+                }
+            }
+            Binding::Mouse(MouseButton::Left) => "Left click".to_string(),
+            Binding::Mouse(MouseButton::Right) => "Right click".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "Middle click".to_string(),
+            Binding::Mouse(MouseButton::Other(button)) => format!("Mouse {}", button),
+            Binding::WheelUp => "Wheel up".to_string(),
+            Binding::WheelDown => "Wheel down".to_string(),
+        }
+    }
+}
+
+pub fn gamepad_label(button: GamepadButtonType) -> String {
+    match button {
+        GamepadButtonType::LeftTrigger => "Left bumper".to_string(),
+        GamepadButtonType::RightTrigger => "Right bumper".to_string(),
+        GamepadButtonType::LeftTrigger2 => "Left trigger".to_string(),
+        GamepadButtonType::RightTrigger2 => "Right trigger".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+// Key bindings for every action, loaded from and saved to the user's config folder.
+// The sticks always move and aim, gamepad buttons are bound on top of that.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InputMap {
+    #[serde(default)]
+    pub keyboard: BTreeMap<Action, Binding>,
+    #[serde(default)]
+    pub gamepad: BTreeMap<Action, GamepadButtonType>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let keyboard = BTreeMap::from([
+            (Action::MoveUp, Binding::Key(KeyCode::W)),
+            (Action::MoveDown, Binding::Key(KeyCode::S)),
+            (Action::MoveLeft, Binding::Key(KeyCode::A)),
+            (Action::MoveRight, Binding::Key(KeyCode::D)),
+            (Action::Fire, Binding::Mouse(MouseButton::Left)),
+            (Action::Reload, Binding::Key(KeyCode::R)),
+            (Action::NextWeapon, Binding::WheelDown),
+            (Action::PreviousWeapon, Binding::WheelUp),
+            (Action::Weapon1, Binding::Key(KeyCode::Key1)),
+            (Action::Weapon2, Binding::Key(KeyCode::Key2)),
+            (Action::Weapon3, Binding::Key(KeyCode::Key3)),
+            (Action::UseMedkit, Binding::Key(KeyCode::Q)),
+            (Action::Pause, Binding::Key(KeyCode::Escape)),
+        ]);
+        let gamepad = BTreeMap::from([
+            (Action::Fire, GamepadButtonType::RightTrigger2),
+            (Action::Reload, GamepadButtonType::West),
+            (Action::NextWeapon, GamepadButtonType::RightTrigger),
+            (Action::PreviousWeapon, GamepadButtonType::LeftTrigger),
+            (Action::UseMedkit, GamepadButtonType::DPadUp),
+            (Action::Pause, GamepadButtonType::Start),
+        ]);
+        InputMap {
+            keyboard: keyboard,
+            gamepad: gamepad,
+        }
+    }
+}
+
+impl InputMap {
+    pub fn path() -> PathBuf {
+        config_dir().join("controls.json")
+    }
+
+    // A missing file means nothing has been rebound yet. A broken one shouldn't stop
+    // the game starting, so fall back to the defaults and say why.
+    pub fn load() -> InputMap {
+        let mut map = match read_json::<InputMap>(&InputMap::path()) {
+            Ok(Some(map)) => map,
+            Ok(None) => return InputMap::default(),
+            Err(err) => {
+                warn!("Couldn't read {}, using the default controls", err);
+                return InputMap::default();
+            }
+        };
+
+        // Actions added since the file was saved get their default, unless it's been taken
+        let defaults = InputMap::default();
+        for (action, binding) in defaults.keyboard {
+            if !map.keyboard.contains_key(&action) && map.keyboard_action(binding).is_none() {
+                map.keyboard.insert(action, binding);
+            }
+        }
+        for (action, button) in defaults.gamepad {
+            if !map.gamepad.contains_key(&action) && map.gamepad_action(button).is_none() {
+                map.gamepad.insert(action, button);
+            }
+        }
+        map
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_json(&InputMap::path(), self)
+    }
+
+    pub fn keyboard_action(&self, binding: Binding) -> Option<Action> {
+        self.keyboard.iter().find(|(_, bound)| **bound == binding).map(|(action, _)| *action)
+    }
+
+    pub fn gamepad_action(&self, button: GamepadButtonType) -> Option<Action> {
+        self.gamepad.iter().find(|(_, bound)| **bound == button).map(|(action, _)| *action)
+    }
+
+    // Binds the input to the action. If another action was already using it the two swap,
+    // and that action is returned so the player can be told.
+    pub fn bind_keyboard(&mut self, action: Action, binding: Binding) -> Option<Action> {
+        let conflict = self.keyboard_action(binding).filter(|other| *other != action);
+        let previous = self.keyboard.insert(action, binding);
+        if let Some(other) = conflict {
+            match previous {
+                Some(previous) => self.keyboard.insert(other, previous),
+                None => self.keyboard.remove(&other),
+            };
+        }
+        conflict
+    }
+
+    pub fn bind_gamepad(&mut self, action: Action, button: GamepadButtonType) -> Option<Action> {
+        let conflict = self.gamepad_action(button).filter(|other| *other != action);
+        let previous = self.gamepad.insert(action, button);
+        if let Some(other) = conflict {
+            match previous {
+                Some(previous) => self.gamepad.insert(other, previous),
+                None => self.gamepad.remove(&other),
+            };
+        }
+        conflict
+    }
+}
+
+// Whichever device was touched last is the one we listen to
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActiveDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad(Gamepad),
+}
+
+// What the player wants to do in the next game tick, whatever they are holding
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerInput {
+    // Up to one long, shorter when a stick is only part way over
+    pub movement: Vec2,
+    // Direction the right stick is pushed, when it is out of the deadzone
+    pub aim: Option<Vec2>,
+    pub fire: bool,
+    pub fire_pressed: bool,
+    pub reload: bool,
+    pub select_weapon: Option<usize>,
+    // Weapons to move along by, negative for back
+    pub cycle_weapon: i32,
+    pub use_medkit: bool,
+    pub pause: bool,
+}
+
+impl PlayerInput {
+    // Once a game tick has acted on them. Pause is left alone, it's read every frame rather than every tick.
+    pub fn clear_presses(&mut self) {
+        self.fire_pressed = false;
+        self.reload = false;
+        self.select_weapon = None;
+        self.cycle_weapon = 0;
+        self.use_medkit = false;
+    }
+}
+
+// Looks actions up in the input map for whichever device is in use
+struct ActionReader<'a> {
+    device: ActiveDevice,
+    map: &'a InputMap,
+    keys: &'a Input<KeyCode>,
+    mouse_buttons: &'a Input<MouseButton>,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    wheel_up: bool,
+    wheel_down: bool,
+}
+
+impl ActionReader<'_> {
+    // A wheel notch counts as both held and pressed for the frame it happens
+    fn check(&self, action: Action, held: bool) -> bool {
+        match self.device {
+            ActiveDevice::KeyboardMouse => match self.map.keyboard.get(&action) {
+                Some(Binding::Key(key)) => {
+                    if held { self.keys.pressed(*key) } else { self.keys.just_pressed(*key) }
+                }
+                Some(Binding::Mouse(button)) => {
+                    if held { self.mouse_buttons.pressed(*button) } else { self.mouse_buttons.just_pressed(*button) }
+                }
+                Some(Binding::WheelUp) => self.wheel_up,
+                Some(Binding::WheelDown) => self.wheel_down,
+                None => false,
+            },
+            ActiveDevice::Gamepad(gamepad) => match self.map.gamepad.get(&action) {
+                Some(button_type) => {
+                    let button = GamepadButton::new(gamepad, *button_type);
+                    if held { self.gamepad_buttons.pressed(button) } else { self.gamepad_buttons.just_pressed(button) }
+                }
+                None => false,
+            },
+        }
+    }
+
+    fn pressed(&self, action: Action) -> bool {
+        self.check(action, true)
+    }
+
+    fn just_pressed(&self, action: Action) -> bool {
+        self.check(action, false)
+    }
+}
+
+// Scales a stick so it reads zero at the edge of the deadzone and one when pushed all the way
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length < STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+    stick / length * scaled
+}
+
+fn read_stick(axes: &Axis<GamepadAxis>, gamepad: Gamepad, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+    Vec2::new(
+        axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.0),
+        axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.0),
+    )
+}
+
+pub fn detect_active_device(
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut cursor: EventReader<CursorMoved>,
+    mut wheel: EventReader<MouseWheel>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut device: ResMut<ActiveDevice>,
+){
+    // Unplugging the pad hands control back to the keyboard
+    if let ActiveDevice::Gamepad(gamepad) = *device {
+        if !gamepads.contains(gamepad) {
+            *device = ActiveDevice::KeyboardMouse;
+        }
+    }
+
+    let mouse_moved = cursor.iter().count() > 0;
+    let wheel_moved = wheel.iter().count() > 0;
+    if keys.get_just_pressed().next().is_some() || mouse_buttons.get_just_pressed().next().is_some()
+        || mouse_moved || wheel_moved {
+        *device = ActiveDevice::KeyboardMouse;
+    }
+
+    if let Some(button) = gamepad_buttons.get_just_pressed().next() {
+        *device = ActiveDevice::Gamepad(button.gamepad);
+    }
+    for gamepad in gamepads.iter() {
+        let left = read_stick(&axes, gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        let right = read_stick(&axes, gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+        if apply_deadzone(left) != Vec2::ZERO || apply_deadzone(right) != Vec2::ZERO {
+            *device = ActiveDevice::Gamepad(gamepad);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn read_player_input(
+    device: Res<ActiveDevice>,
+    map: Res<InputMap>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut wheel: EventReader<MouseWheel>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut input: ResMut<PlayerInput>,
+){
+    // Whatever is held is read fresh every frame, but presses are kept until a game tick has
+    // used them, a fast frame can go by without one
+    input.movement = Vec2::ZERO;
+    input.aim = None;
+
+    // Always drain the wheel, so nothing stale is left over when switching back to the mouse
+    let mut wheel_up = false;
+    let mut wheel_down = false;
+    for ev in wheel.iter() {
+        wheel_up |= ev.y > 0.0;
+        wheel_down |= ev.y < 0.0;
+    }
+
+    let actions = ActionReader {
+        device: *device,
+        map: &map,
+        keys: &keys,
+        mouse_buttons: &mouse_buttons,
+        gamepad_buttons: &gamepad_buttons,
+        wheel_up: wheel_up,
+        wheel_down: wheel_down,
+    };
+
+    for (action, direction) in [
+        (Action::MoveUp, Vec2::Y),
+        (Action::MoveDown, Vec2::NEG_Y),
+        (Action::MoveLeft, Vec2::NEG_X),
+        (Action::MoveRight, Vec2::X),
+    ] {
+        if actions.pressed(action) {
+            input.movement += direction;
+        }
+    }
+
+    if let ActiveDevice::Gamepad(gamepad) = *device {
+        input.movement += apply_deadzone(
+            read_stick(&axes, gamepad, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY)
+        );
+        let aim = apply_deadzone(
+            read_stick(&axes, gamepad, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY)
+        );
+        if aim != Vec2::ZERO {
+            input.aim = Some(aim.normalize());
+        }
+    }
+    // No faster on the diagonal than a stick pushed the same way
+    input.movement = input.movement.clamp_length_max(1.0);
+
+    input.fire = actions.pressed(Action::Fire);
+    input.fire_pressed |= actions.just_pressed(Action::Fire);
+    input.reload |= actions.just_pressed(Action::Reload);
+    input.select_weapon = [Action::Weapon1, Action::Weapon2, Action::Weapon3]
+        .iter()
+        .position(|action| actions.just_pressed(*action))
+        .or(input.select_weapon);
+    if actions.just_pressed(Action::NextWeapon) {
+        input.cycle_weapon += 1;
+    }
+    if actions.just_pressed(Action::PreviousWeapon) {
+        input.cycle_weapon -= 1;
+    }
+    input.use_medkit |= actions.just_pressed(Action::UseMedkit);
+    input.pause = actions.just_pressed(Action::Pause);
+}
+
+// Last thing in every game tick
+pub fn consume_presses(mut input: ResMut<PlayerInput>) {
+    input.clear_presses();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_to_a_free_key() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind_keyboard(Action::Reload, Binding::Key(KeyCode::F)), None);
+        assert_eq!(map.keyboard[&Action::Reload], Binding::Key(KeyCode::F));
+        assert_eq!(map.keyboard_action(Binding::Key(KeyCode::R)), None);
+    }
+
+    #[test]
+    fn bind_to_a_used_key_swaps() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind_keyboard(Action::MoveUp, Binding::Key(KeyCode::D)), Some(Action::MoveRight));
+        assert_eq!(map.keyboard[&Action::MoveUp], Binding::Key(KeyCode::D));
+        assert_eq!(map.keyboard[&Action::MoveRight], Binding::Key(KeyCode::W));
+        assert_eq!(map.keyboard.len(), InputMap::default().keyboard.len());
+    }
+
+    #[test]
+    fn bind_across_keys_and_mouse_swaps() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind_keyboard(Action::Reload, Binding::Mouse(MouseButton::Left)), Some(Action::Fire));
+        assert_eq!(map.keyboard[&Action::Fire], Binding::Key(KeyCode::R));
+        assert_eq!(map.keyboard[&Action::Reload], Binding::Mouse(MouseButton::Left));
+    }
+
+    #[test]
+    fn unbound_action_takes_the_key() {
+        // Nothing to swap back, so the other action is left unbound
+        let mut map = InputMap::default();
+        map.keyboard.remove(&Action::UseMedkit);
+        assert_eq!(map.bind_keyboard(Action::UseMedkit, Binding::Key(KeyCode::R)), Some(Action::Reload));
+        assert_eq!(map.keyboard[&Action::UseMedkit], Binding::Key(KeyCode::R));
+        assert!(!map.keyboard.contains_key(&Action::Reload));
+    }
+
+    #[test]
+    fn rebinding_the_same_key_changes_nothing() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind_keyboard(Action::Pause, Binding::Key(KeyCode::Escape)), None);
+        assert_eq!(map.keyboard, InputMap::default().keyboard);
+    }
+
+    #[test]
+    fn gamepad_bindings_swap_too() {
+        let mut map = InputMap::default();
+        assert_eq!(map.bind_gamepad(Action::Fire, GamepadButtonType::West), Some(Action::Reload));
+        assert_eq!(map.gamepad[&Action::Fire], GamepadButtonType::West);
+        assert_eq!(map.gamepad[&Action::Reload], GamepadButtonType::RightTrigger2);
+
+        // Weapon 1 has no button by default
+        assert_eq!(map.bind_gamepad(Action::Weapon1, GamepadButtonType::Start), Some(Action::Pause));
+        assert_eq!(map.gamepad_action(GamepadButtonType::Start), Some(Action::Weapon1));
+        assert!(!map.gamepad.contains_key(&Action::Pause));
+    }
+
+    #[test]
+    fn keyboard_and_gamepad_are_separate() {
+        let mut map = InputMap::default();
+        map.bind_gamepad(Action::Reload, GamepadButtonType::South);
+        assert_eq!(map.keyboard, InputMap::default().keyboard);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut map = InputMap::default();
+        map.bind_keyboard(Action::Fire, Binding::WheelUp);
+        let loaded: InputMap = serde_json::from_str(&serde_json::to_string(&map).unwrap()).unwrap();
+        assert_eq!(loaded.keyboard, map.keyboard);
+        assert_eq!(loaded.gamepad, map.gamepad);
+    }
+}
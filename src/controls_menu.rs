@@ -0,0 +1,358 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+use std::path::Path;
+
+use super::{despawn_screen, MainGameState};
+use crate::controls::{self, Action, Binding, InputMap};
+use crate::menu::{MenuState, TEXT_COLOR, BACKGROUND_COLOR, NORMAL_BUTTON};
+use crate::pause::PauseState;
+
+pub struct ControlsMenuPlugin;
+
+impl Plugin for ControlsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // The same screen is opened from the main menu and from the pause overlay
+            .add_systems(OnEnter(MenuState::Controls), controls_menu_setup)
+            .add_systems(OnEnter(PauseState::Controls), controls_menu_setup)
+            .add_systems(
+                Update,
+                (
+                    capture_binding.before(controls_menu_action),
+                    controls_menu_action,
+                    update_binding_labels.after(controls_menu_action),
+                ).run_if(in_state(MenuState::Controls).or_else(in_state(PauseState::Controls))),
+            )
+            .add_systems(OnExit(MenuState::Controls), despawn_screen::<OnControlsMenuScreen>)
+            .add_systems(OnExit(PauseState::Controls), despawn_screen::<OnControlsMenuScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnControlsMenuScreen;
+
+// The two columns of bindings on the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BindingColumn {
+    KeyboardMouse,
+    Gamepad,
+}
+
+#[derive(Component)]
+enum ControlsButtonAction {
+    Rebind(Action, BindingColumn),
+    ResetDefaults,
+    Back,
+}
+
+#[derive(Component)]
+struct BindingLabel(Action, BindingColumn);
+
+#[derive(Component)]
+struct StatusLabel;
+
+// Which binding we are waiting on a key for, and what to tell the player
+#[derive(Resource)]
+struct ControlsMenu {
+    waiting: Option<(Action, BindingColumn)>,
+    status: String,
+}
+
+const CLICK_TO_CHANGE: &str = "Click a binding to change it";
+
+fn controls_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    commands.insert_resource(ControlsMenu {
+        waiting: None,
+        status: CLICK_TO_CHANGE.to_string(),
+    });
+
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let binding_style = Style {
+        width: Val::Px(220.0),
+        height: Val::Px(30.0),
+        margin: UiRect::horizontal(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let small_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 22.0,
+        color: TEXT_COLOR,
+    };
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                padding: UiRect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(OnControlsMenuScreen)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Controls",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 50.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+
+            for action in Action::ALL {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::vertical(Val::Px(2.0)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(
+                            TextBundle::from_section(action.label(), small_text_style.clone())
+                                .with_style(Style {
+                                    width: Val::Px(200.0),
+                                    ..default()
+                                }),
+                        );
+
+                        for column in [BindingColumn::KeyboardMouse, BindingColumn::Gamepad] {
+                            row
+                                .spawn(ButtonBundle {
+                                    style: binding_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                })
+                                .insert(ControlsButtonAction::Rebind(action, column))
+                                .with_children(|button| {
+                                    button
+                                        .spawn(TextBundle::from_section("", small_text_style.clone()))
+                                        .insert(BindingLabel(action, column));
+                                });
+                        }
+                    });
+            }
+
+            parent
+                .spawn(
+                    TextBundle::from_section(CLICK_TO_CHANGE, small_text_style.clone())
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        }),
+                )
+                .insert(StatusLabel);
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    row
+                        .spawn(ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ControlsButtonAction::ResetDefaults)
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section("Reset", button_text_style.clone()));
+                        });
+
+                    row
+                        .spawn(ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(ControlsButtonAction::Back)
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section("Back", button_text_style.clone()));
+                        });
+                });
+        });
+}
+
+fn save_bindings(input_map: &InputMap, controls_menu: &mut ControlsMenu) {
+    if let Err(err) = input_map.save() {
+        controls_menu.status = format!("Couldn't save controls: {}", err);
+    }
+}
+
+// Back to the settings screen it was opened from
+fn leave_controls(
+    game_state: &State<MainGameState>,
+    menu_state: &mut NextState<MenuState>,
+    pause_state: &mut NextState<PauseState>,
+){
+    if *game_state.get() == MainGameState::Game {
+        pause_state.set(PauseState::Settings);
+    } else {
+        menu_state.set(MenuState::Settings);
+    }
+}
+
+fn controls_menu_action(
+    interaction_query: Query<
+        (&Interaction, &ControlsButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut input_map: ResMut<InputMap>,
+    mut controls_menu: ResMut<ControlsMenu>,
+    game_state: Res<State<MainGameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+){
+    for (interaction, button_action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button_action {
+            ControlsButtonAction::Rebind(action, column) => {
+                controls_menu.waiting = Some((*action, *column));
+                controls_menu.status = match column {
+                    // Escape always backs out, so it's the one key that can't be bound
+                    BindingColumn::KeyboardMouse => format!("Press a key or mouse button for {} (Esc cancels, it can't be bound)", action.label()),
+                    BindingColumn::Gamepad => format!("Press a gamepad button for {} (Esc to cancel)", action.label()),
+                };
+            }
+            ControlsButtonAction::ResetDefaults => {
+                *input_map = InputMap::default();
+                controls_menu.waiting = None;
+                controls_menu.status = "Controls reset to the defaults".to_string();
+                save_bindings(&input_map, &mut controls_menu);
+            }
+            ControlsButtonAction::Back => leave_controls(&game_state, &mut menu_state, &mut pause_state),
+        }
+    }
+}
+
+// Runs before the buttons are checked, so the click that starts a rebind isn't taken as the new binding
+#[allow(clippy::too_many_arguments)]
+fn capture_binding(
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut wheel: EventReader<MouseWheel>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut input_map: ResMut<InputMap>,
+    mut controls_menu: ResMut<ControlsMenu>,
+    game_state: Res<State<MainGameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+){
+    let wheel_binding = wheel.iter()
+        .filter(|ev| ev.y != 0.0)
+        .last()
+        .map(|ev| if ev.y > 0.0 { Binding::WheelUp } else { Binding::WheelDown });
+
+    // Escape backs out of a rebind, or off the screen when there isn't one going
+    if keys.just_pressed(KeyCode::Escape) {
+        if controls_menu.waiting.is_some() {
+            controls_menu.waiting = None;
+            controls_menu.status = CLICK_TO_CHANGE.to_string();
+        } else {
+            leave_controls(&game_state, &mut menu_state, &mut pause_state);
+        }
+        return;
+    }
+
+    let Some((action, column)) = controls_menu.waiting else {
+        return;
+    };
+
+    let conflict = match column {
+        BindingColumn::KeyboardMouse => {
+            let binding = keys.get_just_pressed().next().map(|key| Binding::Key(*key))
+                .or_else(|| mouse_buttons.get_just_pressed().next().map(|button| Binding::Mouse(*button)))
+                .or(wheel_binding);
+            let Some(binding) = binding else {
+                return;
+            };
+            controls_menu.status = format!("{} is now {}", action.label(), binding.label());
+            input_map.bind_keyboard(action, binding)
+        }
+        BindingColumn::Gamepad => {
+            let Some(button) = gamepad_buttons.get_just_pressed().next() else {
+                return;
+            };
+            controls_menu.status = format!("{} is now {}", action.label(), controls::gamepad_label(button.button_type));
+            input_map.bind_gamepad(action, button.button_type)
+        }
+    };
+
+    if let Some(other) = conflict {
+        let swapped = match column {
+            BindingColumn::KeyboardMouse => input_map.keyboard.contains_key(&other),
+            BindingColumn::Gamepad => input_map.gamepad.contains_key(&other),
+        };
+        controls_menu.status = if swapped {
+            format!("{}, swapped with {}", controls_menu.status, other.label())
+        } else {
+            format!("{}, taken from {}", controls_menu.status, other.label())
+        };
+    }
+    controls_menu.waiting = None;
+    save_bindings(&input_map, &mut controls_menu);
+}
+
+fn update_binding_labels(
+    input_map: Res<InputMap>,
+    controls_menu: Res<ControlsMenu>,
+    mut labels: Query<(&BindingLabel, &mut Text)>,
+    mut status: Query<&mut Text, (With<StatusLabel>, Without<BindingLabel>)>,
+){
+    if !input_map.is_changed() && !controls_menu.is_changed() {
+        return;
+    }
+
+    for (BindingLabel(action, column), mut text) in labels.iter_mut() {
+        text.sections[0].value = if controls_menu.waiting == Some((*action, *column)) {
+            "...".to_string()
+        } else {
+            match column {
+                BindingColumn::KeyboardMouse => input_map.keyboard.get(action).map(|binding| binding.label()),
+                BindingColumn::Gamepad => input_map.gamepad.get(action).map(|button| controls::gamepad_label(*button)),
+            }.unwrap_or_else(|| "-".to_string())
+        };
+    }
+
+    for mut text in status.iter_mut() {
+        text.sections[0].value = controls_menu.status.clone();
+    }
+}
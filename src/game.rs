@@ -1,44 +1,125 @@
 use bevy::prelude::*; 
-use std::path::Path;
+use bevy::ecs::schedule::SystemConfigs;
+use bevy::input::InputSystem;
 
 use super::{
     despawn_screen,
     MainGameState,
-    menu::MenuState,
+    pause::PauseState,
     player,
     bullet,
     blood,
     zombie,
+    level,
+    navigation,
+    waves,
+    weapon,
+    inventory,
+    camera,
+    controls,
+    hud,
+    score,
+    save,
+    rng,
+    replay,
+    interpolation,
     GameDetails
 };
 
-use crate::{GAME_WIDTH,GAME_HEIGHT};
+// Gameplay steps this many times a second whatever the frame rate, so a run plays out the same
+// on every machine and a slow frame can't make a bullet skip over a zombie
+pub const TICK_RATE: f32 = 60.0;
 
 pub struct GamePlugin;
 
-use crate::player::Player;
-
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(MainGameState::Game), game_setup)
+            .add_event::<waves::WaveStarted>()
+            .add_event::<waves::WaveCleared>()
+            .add_event::<inventory::GrantItem>()
+            .add_event::<hud::HealthChanged>()
+            .add_event::<hud::AmmoChanged>()
+            .add_event::<hud::ScoreChanged>()
+            .add_event::<hud::ZombieKilled>()
+            .init_resource::<controls::ActiveDevice>()
+            .init_resource::<controls::PlayerInput>()
+            .add_systems(PreUpdate, (
+                controls::detect_active_device,
+                controls::read_player_input.after(controls::detect_active_device),
+            ).after(InputSystem).run_if(in_state(MainGameState::Game)))
+            .add_systems(OnEnter(MainGameState::Game), (
+                game_setup,
+                // Nothing pressed before the run started carries over into it
+                controls::consume_presses,
+                replay::start_recording.after(game_setup).run_if(not(resource_exists::<replay::ReplayPlayback>())),
+                replay::start_playback.run_if(resource_exists::<replay::ReplayPlayback>()),
+            ))
+            .add_systems(OnExit(MainGameState::Game), replay::save_recording)
+            .add_systems(Update, replay::replay_from_menu
+                .run_if(in_state(MainGameState::Menu).and_then(resource_exists::<replay::ReplayPlayback>())))
+            // The game itself moves on in ticks, one system after another in the same order every
+            // time. It stops while the pause overlay is up, and for good once the player is dead.
+            .insert_resource(FixedTime::new_from_secs(1.0 / TICK_RATE))
+            .add_systems(FixedUpdate, simulation().run_if(in_state(MainGameState::Game)
+                .and_then(in_state(PauseState::Running))
+                .and_then(player::player_alive)))
+            // Drawing happens every frame, between the last two ticks. Input is still read while
+            // paused so it can be unpaused.
             .add_systems(Update, (
-                menu_return_check,
-                background_mapper,
                 game_update,
-                zombie::zombie_mover,
-                zombie::zombie_checker,
-                player::player_mover,
-                player::track_mouse,
-                player::fire_controller,
-                bullet::bullet_mover,
-                bullet::bullet_collision,
-                blood::update_blood_spatter,
-            ).run_if(in_state(MainGameState::Game)))
+                interpolation::interpolate,
+                camera::camera_follow.after(interpolation::interpolate),
+                player::track_mouse.after(camera::camera_follow)
+                    .run_if(not(resource_exists::<replay::ReplayPlayback>())),
+                player::track_stick
+                    .run_if(not(resource_exists::<replay::ReplayPlayback>())),
+                hud::update_health,
+                hud::update_ammo,
+                hud::update_score,
+                hud::update_wave,
+            ).run_if(in_state(MainGameState::Game).and_then(in_state(PauseState::Running))))
             .add_systems(OnExit(MainGameState::Game), despawn_screen::<OnGameScreen>);
     }
 }
 
+// One tick of the game, everything that decides how a run plays out. The replay test runs
+// this too, so it checks the order the game really uses.
+pub fn simulation() -> SystemConfigs {
+    (
+        (
+            interpolation::begin_tick,
+            replay::record_tick.run_if(resource_exists::<replay::ReplayRecorder>()),
+            replay::play_tick.run_if(resource_exists::<replay::ReplayPlayback>()),
+        ).chain(),
+        (
+            level::stream_tiles,
+            player::player_mover,
+            weapon::weapon_switch,
+            weapon::weapon_reload,
+            player::fire_controller,
+            inventory::use_consumables,
+        ).chain(),
+        (
+            zombie::zombie_perception,
+            zombie::zombie_mover,
+            zombie::zombie_attack,
+            player::player_health,
+        ).chain(),
+        (
+            waves::wave_director,
+            bullet::bullet_mover,
+            bullet::bullet_collision,
+            blood::update_blood_spatter,
+            inventory::wave_rewards,
+            inventory::grant_items,
+            score::score_events,
+            run_timer,
+        ).chain(),
+        controls::consume_presses,
+    ).chain().into_configs()
+}
+
 #[derive(Component)]
 pub struct OnGameScreen;
 
@@ -51,45 +132,89 @@ pub struct AnimationIndices {
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
 
-#[derive(Component)]
-struct BackgroundTile{
-    x: u32,
-    y: u32,
+// How the current run is going, kept after the game ends for the results screen
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub time_survived: f32,
+    pub zombies_killed: u32,
+    pub score: u32,
+    // Highest wave reached
+    pub wave: u32,
+    // Bullets that found a zombie, for working out accuracy
+    pub shots_hit: u32,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn game_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    game_details: Res<GameDetails>
+    mut game_details: ResMut<GameDetails>,
+    mut health: EventWriter<hud::HealthChanged>,
+    mut ammo: EventWriter<hud::AmmoChanged>,
+    mut score: EventWriter<hud::ScoreChanged>,
+    mut started: EventWriter<waves::WaveStarted>,
+    pending_load: Option<Res<save::PendingLoad>>,
+    mut game_rng: ResMut<rng::GameRng>,
+    playback: Option<Res<replay::ReplayPlayback>>,
 ){
-    commands
-        .spawn(Camera2dBundle::default())
-        .insert(OnGameScreen);
+    // A replay plays out on the seed and level it was recorded with
+    match &playback {
+        Some(playback) => game_rng.reseed(playback.replay.seed),
+        None => game_rng.start_run(),
+    }
+    camera::spawn_game_camera(&mut commands);
+    commands.insert_resource(RunStats::default());
+    commands.insert_resource(score::Scoring::default());
     
-    player::create_player(&mut commands, &asset_server, &mut texture_atlases);
+    let player_entity = player::create_player(&mut commands, &asset_server, &mut texture_atlases);
+
+    // Fill the HUD in with what the player starts with
+    hud::spawn_hud(&mut commands, &asset_server);
+    health.send(hud::HealthChanged { health: player::PLAYER_MAX_HEALTH });
+    ammo.send(hud::AmmoChanged::new(&inventory::Inventory::default()));
 
-    // Scenery and Background
+    // Scenery and Background are streamed in around the player from the level tiles
     {
-        for x in 0..game_details.width {
-            for y in 0..game_details.height {
-                let texture_path = Path::new("images").join("scenery").join("street_scene.png");
-                let texture_handle = asset_server.load(texture_path);
-                
-                commands.spawn((
-                    SpriteBundle {
-                        texture: texture_handle,
-                        transform: Transform::from_xyz(x as f32 * GAME_WIDTH, y as f32 * GAME_HEIGHT, -1.0).with_scale(Vec3::splat(1.0)),
-                        ..default()
-                    },
-                ))
-                .insert(BackgroundTile{
-                    x: x,
-                    y: y,
-                })
-                .insert(OnGameScreen);    
-            }
-        }   
+        // Better to stop here than play in a half built world
+        let tiles = match &playback {
+            Some(playback) => level::LevelTiles::from_levels(playback.replay.levels.clone()),
+            None => level::LevelTiles::load()
+                .unwrap_or_else(|err| panic!("Failed to load levels\n{}", err)),
+        };
+        game_details.bounds = tiles.bounds();
+        commands.insert_resource(navigation::NavGrid::new(&tiles));
+
+        // Zombies arrive in waves, spawning at the enemies listed in the levels
+        let config = match &playback {
+            Some(playback) => playback.replay.waves.clone(),
+            None => waves::WaveConfig::load()
+                .unwrap_or_else(|err| panic!("Failed to load waves\n{}", err)),
+        };
+        let mut director = waves::WaveDirector::new(config, &tiles);
+
+        // Continuing a saved run puts everything back on top of the fresh start
+        if let Some(pending_load) = pending_load {
+            commands.remove_resource::<save::PendingLoad>();
+            save::restore_run(
+                &pending_load.0,
+                &mut commands,
+                &asset_server,
+                &mut texture_atlases,
+                player_entity,
+                &mut game_rng,
+                &mut director,
+                &mut health,
+                &mut ammo,
+                &mut score,
+                &mut started,
+            );
+            // Now the run carries on from here it can't be continued twice
+            save::SaveGame::delete();
+        }
+
+        commands.insert_resource(director);
+        commands.insert_resource(tiles);
     }
 }
 
@@ -113,25 +238,9 @@ fn game_update(
     }
 }
 
-fn menu_return_check(
-    keys: Res<Input<KeyCode>>,
-    mut menu_state: ResMut<NextState<MenuState>>,
-    mut game_state: ResMut<NextState<MainGameState>>,
+fn run_timer(
+    time: Res<FixedTime>,
+    mut stats: ResMut<RunStats>,
 ){
-    if keys.just_pressed(KeyCode::Escape){
-        game_state.set(MainGameState::Menu);
-        menu_state.set(MenuState::Main);
-        return;
-    }
+    stats.time_survived += time.period.as_secs_f32();
 }
-
-fn background_mapper(
-    mut _commands: Commands,
-    game_details: Res<GameDetails>,
-    mut backgrounds: Query<(&BackgroundTile, &mut Transform), (Without<Player>,With<BackgroundTile>)>,
-){
-    for (tile, mut transform) in backgrounds.iter_mut() {
-        transform.translation.x = (tile.x as f32 * GAME_WIDTH) - game_details.offset_x;
-        transform.translation.y = (tile.y as f32 * GAME_HEIGHT) - game_details.offset_y;
-    }
-}
\ No newline at end of file
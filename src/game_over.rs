@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use std::path::Path;
+
+use super::{despawn_screen,MainGameState};
+use crate::menu::{self, MenuState, TEXT_COLOR, BACKGROUND_COLOR, NORMAL_BUTTON};
+use crate::game::RunStats;
+use crate::score::{HighScore, HighScores, MAX_NAME_LENGTH};
+use crate::replay::ReplayPlayback;
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(MainGameState::GameOver), game_over_setup)
+            .add_systems(
+                Update,
+                (game_over_action, menu::button_system).run_if(in_state(MainGameState::GameOver)),
+            )
+            .add_systems(
+                Update,
+                (
+                    enter_name.before(game_over_action),
+                    update_name_entry.after(game_over_action),
+                ).run_if(in_state(MainGameState::GameOver).and_then(resource_exists::<NameEntry>())),
+            )
+            .add_systems(OnExit(MainGameState::GameOver), (despawn_screen::<OnGameOverScreen>, remove_name_entry));
+    }
+}
+
+#[derive(Component)]
+pub struct OnGameOverScreen;
+
+#[derive(Component)]
+enum GameOverButtonAction {
+    SaveScore,
+    Retry,
+    MainMenu,
+}
+
+#[derive(Component)]
+struct NameText;
+
+// Only there when the run made the high score table
+#[derive(Resource)]
+struct NameEntry {
+    name: String,
+    saved: bool,
+    status: String,
+}
+
+const NAME_PROMPT: &str = "New high score! Type your name";
+
+fn game_over_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    stats: Res<RunStats>,
+    playback: Option<Res<ReplayPlayback>>,
+){
+    commands
+        .spawn(Camera2dBundle::default())
+        .insert(OnGameOverScreen);
+
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(20.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 40.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    let minutes = (stats.time_survived / 60.0) as u32;
+    let seconds = (stats.time_survived % 60.0) as u32;
+    let results = format!(
+        "Survived {}:{:02}\nZombies killed: {}\nScore: {}",
+        minutes, seconds, stats.zombies_killed, stats.score
+    );
+    // Watching a replay doesn't earn a place on the table
+    let high_score = playback.is_none() && HighScores::load().qualifies(stats.score);
+    if high_score {
+        commands.insert_resource(NameEntry {
+            name: String::new(),
+            saved: false,
+            status: NAME_PROMPT.to_string(),
+        });
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(OnGameOverScreen)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Game Over",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 80.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(50.0)),
+                    ..default()
+                }),
+            );
+
+            parent.spawn(
+                TextBundle::from_section(
+                    results,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 40.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(20.0)),
+                    ..default()
+                }),
+            );
+
+            if high_score {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row
+                            .spawn(
+                                TextBundle::from_section(
+                                    NAME_PROMPT,
+                                    TextStyle {
+                                        font: font.clone(),
+                                        font_size: 40.0,
+                                        color: TEXT_COLOR,
+                                    },
+                                )
+                                .with_style(Style {
+                                    width: Val::Px(450.0),
+                                    margin: UiRect::all(Val::Px(20.0)),
+                                    ..default()
+                                }),
+                            )
+                            .insert(NameText);
+
+                        row
+                            .spawn(ButtonBundle {
+                                style: button_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(GameOverButtonAction::SaveScore)
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("Save", button_text_style.clone()));
+                            });
+                    });
+            }
+
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(GameOverButtonAction::Retry)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Retry", button_text_style.clone()));
+                });
+
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(GameOverButtonAction::MainMenu)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Main Menu", button_text_style.clone()));
+                });
+        });
+}
+
+fn game_over_action(
+    interaction_query: Query<
+        (&Interaction, &GameOverButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+    mut name_entry: Option<ResMut<NameEntry>>,
+    stats: Res<RunStats>,
+) {
+    for (interaction, game_over_button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match game_over_button_action {
+                GameOverButtonAction::SaveScore => {
+                    if let Some(name_entry) = name_entry.as_mut() {
+                        save_high_score(name_entry, &stats);
+                    }
+                }
+                GameOverButtonAction::Retry => {
+                    game_state.set(MainGameState::Game);
+                }
+                GameOverButtonAction::MainMenu => {
+                    game_state.set(MainGameState::Menu);
+                    menu_state.set(MenuState::Main);
+                }
+            }
+        }
+    }
+}
+
+fn save_high_score(name_entry: &mut NameEntry, stats: &RunStats) {
+    if name_entry.saved {
+        return;
+    }
+    let name = name_entry.name.trim();
+    let name = if name.is_empty() { "Anonymous" } else { name };
+
+    let mut high_scores = HighScores::load();
+    let place = high_scores.insert(HighScore {
+        name: name.to_string(),
+        score: stats.score,
+        wave: stats.wave,
+        kills: stats.zombies_killed,
+    });
+    name_entry.saved = true;
+    name_entry.status = match (place, high_scores.save()) {
+        (_, Err(err)) => format!("Couldn't save high score: {}", err),
+        (Some(place), Ok(())) => format!("{} is number {} on the high scores", name, place),
+        // Only if another run beat it to the table in the meantime
+        (None, Ok(())) => "Not quite a high score after all".to_string(),
+    };
+}
+
+fn enter_name(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut name_entry: ResMut<NameEntry>,
+    stats: Res<RunStats>,
+){
+    if name_entry.saved {
+        characters.clear();
+        return;
+    }
+    for character in characters.iter() {
+        if !character.char.is_control() && name_entry.name.chars().count() < MAX_NAME_LENGTH {
+            name_entry.name.push(character.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name_entry.name.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        save_high_score(&mut name_entry, &stats);
+    }
+}
+
+fn update_name_entry(
+    name_entry: Res<NameEntry>,
+    mut texts: Query<&mut Text, With<NameText>>,
+){
+    if !name_entry.is_changed() {
+        return;
+    }
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if name_entry.saved || name_entry.name.is_empty() {
+            name_entry.status.clone()
+        } else {
+            format!("{}_", name_entry.name)
+        };
+    }
+}
+
+fn remove_name_entry(mut commands: Commands) {
+    commands.remove_resource::<NameEntry>();
+}
@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use std::path::Path;
+
+use super::despawn_screen;
+use crate::menu::{MenuState, TEXT_COLOR, BACKGROUND_COLOR, NORMAL_BUTTON};
+use crate::score::HighScores;
+
+pub struct HighScoresMenuPlugin;
+
+impl Plugin for HighScoresMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(MenuState::HighScores), high_scores_menu_setup)
+            .add_systems(Update, high_scores_menu_action.run_if(in_state(MenuState::HighScores)))
+            .add_systems(OnExit(MenuState::HighScores), despawn_screen::<OnHighScoresMenuScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnHighScoresMenuScreen;
+
+#[derive(Component)]
+struct BackButton;
+
+// Widths of the place, name, score and wave columns
+const COLUMNS: [f32; 4] = [60.0, 260.0, 150.0, 100.0];
+
+fn high_scores_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    let high_scores = HighScores::load();
+
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let small_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 26.0,
+        color: TEXT_COLOR,
+    };
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: TEXT_COLOR,
+    };
+
+    let mut rows = vec![["".to_string(), "Name".to_string(), "Score".to_string(), "Wave".to_string()]];
+    for (index, entry) in high_scores.entries.iter().enumerate() {
+        rows.push([
+            format!("{}.", index + 1),
+            entry.name.clone(),
+            entry.score.to_string(),
+            entry.wave.to_string(),
+        ]);
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                padding: UiRect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(OnHighScoresMenuScreen)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "High Scores",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 50.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+
+            if high_scores.entries.is_empty() {
+                parent.spawn(
+                    TextBundle::from_section("No scores yet, go and set one", small_text_style.clone())
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        }),
+                );
+            } else {
+                for row in rows {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Row,
+                                margin: UiRect::vertical(Val::Px(2.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|line| {
+                            for (cell, width) in row.into_iter().zip(COLUMNS) {
+                                line.spawn(
+                                    TextBundle::from_section(cell, small_text_style.clone())
+                                        .with_style(Style {
+                                            width: Val::Px(width),
+                                            ..default()
+                                        }),
+                                );
+                            }
+                        });
+                }
+            }
+
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(BackButton)
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section("Back", button_text_style.clone()));
+                });
+        });
+}
+
+fn high_scores_menu_action(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<BackButton>)>,
+    keys: Res<Input<KeyCode>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+){
+    let pressed = interaction_query.iter().any(|interaction| *interaction == Interaction::Pressed);
+    if pressed || keys.just_pressed(KeyCode::Escape) {
+        menu_state.set(MenuState::Main);
+    }
+}
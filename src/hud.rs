@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use std::path::Path;
+
+use crate::game::OnGameScreen;
+use crate::inventory::Inventory;
+use crate::menu::TEXT_COLOR;
+use crate::player::PLAYER_MAX_HEALTH;
+use crate::waves::WaveStarted;
+
+// Nothing on the HUD is read from the world each frame. The systems that change something
+// send one of these and the HUD picks it up.
+
+#[derive(Event)]
+pub struct HealthChanged {
+    pub health: i32,
+}
+
+#[derive(Event)]
+pub struct AmmoChanged {
+    pub weapon: &'static str,
+    pub in_magazine: u32,
+    pub reserve: u32,
+    pub reloading: bool,
+}
+
+impl AmmoChanged {
+    // Whatever gun is out right now
+    pub fn new(inventory: &Inventory) -> Self {
+        let slot = inventory.loadout.current();
+        AmmoChanged {
+            weapon: slot.weapon.name,
+            in_magazine: slot.in_magazine,
+            reserve: inventory.ammo(slot.weapon.caliber),
+            reloading: slot.is_reloading(),
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct ScoreChanged {
+    pub score: u32,
+    // Combo multiplier the next kill will get
+    pub multiplier: u32,
+}
+
+#[derive(Event)]
+pub struct ZombieKilled {
+    pub at: Vec2,
+}
+
+const HEALTH_BAR_WIDTH: f32 = 200.0;
+const HEALTH_COLOR: Color = Color::rgb(0.75, 0.1, 0.1);
+const HEALTH_BACK_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.6);
+
+#[derive(Component)]
+struct HealthText;
+
+#[derive(Component)]
+struct HealthBar;
+
+#[derive(Component)]
+struct WeaponText;
+
+#[derive(Component)]
+struct AmmoText;
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct WaveText;
+
+#[derive(Component)]
+struct ZombiesText;
+
+// Zombies from the current wave that are still to be killed, spawned or not
+#[derive(Resource, Default)]
+pub struct ZombiesRemaining(pub u32);
+
+pub fn spawn_hud(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+){
+    commands.insert_resource(ZombiesRemaining::default());
+
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let text_style = TextStyle {
+        font: font.clone(),
+        font_size: 28.0,
+        color: TEXT_COLOR,
+    };
+    let big_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 40.0,
+        color: TEXT_COLOR,
+    };
+    let column = |align_items: AlignItems| Style {
+        flex_direction: FlexDirection::Column,
+        align_items: align_items,
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(15.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::FlexStart,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(OnGameScreen)
+        .with_children(|parent| {
+            // Health on the left
+            parent
+                .spawn(NodeBundle { style: column(AlignItems::FlexStart), ..default() })
+                .with_children(|left| {
+                    left
+                        .spawn(TextBundle::from_section("", text_style.clone()))
+                        .insert(HealthText);
+                    left
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(HEALTH_BAR_WIDTH),
+                                height: Val::Px(16.0),
+                                margin: UiRect::top(Val::Px(5.0)),
+                                ..default()
+                            },
+                            background_color: HEALTH_BACK_COLOR.into(),
+                            ..default()
+                        })
+                        .with_children(|bar| {
+                            bar
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        width: Val::Percent(100.0),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    background_color: HEALTH_COLOR.into(),
+                                    ..default()
+                                })
+                                .insert(HealthBar);
+                        });
+                });
+
+            // How the run is going in the middle
+            parent
+                .spawn(NodeBundle { style: column(AlignItems::Center), ..default() })
+                .with_children(|middle| {
+                    middle
+                        .spawn(TextBundle::from_section("Get ready", big_text_style.clone()))
+                        .insert(WaveText);
+                    middle
+                        .spawn(TextBundle::from_section("", text_style.clone()))
+                        .insert(ZombiesText);
+                    middle
+                        .spawn(TextBundle::from_section("Score 0", text_style.clone()))
+                        .insert(ScoreText);
+                });
+
+            // The gun on the right
+            parent
+                .spawn(NodeBundle { style: column(AlignItems::FlexEnd), ..default() })
+                .with_children(|right| {
+                    right
+                        .spawn(TextBundle::from_section("", text_style.clone()))
+                        .insert(WeaponText);
+                    right
+                        .spawn(TextBundle::from_section("", big_text_style.clone()))
+                        .insert(AmmoText);
+                });
+        });
+}
+
+pub fn update_health(
+    mut changes: EventReader<HealthChanged>,
+    mut texts: Query<&mut Text, With<HealthText>>,
+    mut bars: Query<&mut Style, With<HealthBar>>,
+){
+    let Some(change) = changes.iter().last() else {
+        return;
+    };
+    let health = change.health.max(0);
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("Health {}/{}", health, PLAYER_MAX_HEALTH);
+    }
+    for mut style in bars.iter_mut() {
+        style.width = Val::Percent(100.0 * health as f32 / PLAYER_MAX_HEALTH as f32);
+    }
+}
+
+pub fn update_ammo(
+    mut changes: EventReader<AmmoChanged>,
+    mut weapons: Query<&mut Text, (With<WeaponText>, Without<AmmoText>)>,
+    mut ammo: Query<&mut Text, (With<AmmoText>, Without<WeaponText>)>,
+){
+    let Some(change) = changes.iter().last() else {
+        return;
+    };
+
+    for mut text in weapons.iter_mut() {
+        text.sections[0].value = change.weapon.to_string();
+    }
+    for mut text in ammo.iter_mut() {
+        text.sections[0].value = if change.reloading {
+            format!("Reloading / {}", change.reserve)
+        } else {
+            format!("{} / {}", change.in_magazine, change.reserve)
+        };
+    }
+}
+
+pub fn update_score(
+    mut changes: EventReader<ScoreChanged>,
+    mut texts: Query<&mut Text, With<ScoreText>>,
+){
+    let Some(change) = changes.iter().last() else {
+        return;
+    };
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = if change.multiplier > 1 {
+            format!("Score {}  x{}", change.score, change.multiplier)
+        } else {
+            format!("Score {}", change.score)
+        };
+    }
+}
+
+pub fn update_wave(
+    mut started: EventReader<WaveStarted>,
+    mut killed: EventReader<ZombieKilled>,
+    mut remaining: ResMut<ZombiesRemaining>,
+    mut waves: Query<&mut Text, (With<WaveText>, Without<ZombiesText>)>,
+    mut zombies: Query<&mut Text, (With<ZombiesText>, Without<WaveText>)>,
+){
+    let mut changed = false;
+    for wave in started.iter() {
+        remaining.0 = wave.zombies;
+        for mut text in waves.iter_mut() {
+            text.sections[0].value = format!("Wave {}", wave.wave);
+        }
+        changed = true;
+    }
+    for _ in killed.iter() {
+        remaining.0 = remaining.0.saturating_sub(1);
+        changed = true;
+    }
+    if !changed {
+        return;
+    }
+
+    for mut text in zombies.iter_mut() {
+        text.sections[0].value = match remaining.0 {
+            0 => "Wave cleared".to_string(),
+            1 => "1 zombie left".to_string(),
+            count => format!("{} zombies left", count),
+        };
+    }
+}
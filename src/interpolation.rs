@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+
+// Where something was at the end of the last two ticks. Every frame its Transform is put part
+// way between them, by how far the clock has got towards the next tick, so it moves smoothly
+// at any frame rate while the game itself only moves in whole ticks.
+#[derive(Component)]
+pub struct Interpolated {
+    previous: Vec2,
+    pub current: Vec2,
+}
+
+impl Interpolated {
+    pub fn new(at: Vec2) -> Self {
+        Interpolated {
+            previous: at,
+            current: at,
+        }
+    }
+}
+
+// First thing in every tick, the movers then set where things end up
+pub fn begin_tick(mut interpolated: Query<&mut Interpolated>) {
+    for mut interpolated in &mut interpolated {
+        interpolated.previous = interpolated.current;
+    }
+}
+
+pub fn interpolate(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<(&Interpolated, &mut Transform)>,
+){
+    let progress = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0);
+    for (interpolated, mut transform) in &mut query {
+        let at = interpolated.previous.lerp(interpolated.current, progress);
+        transform.translation.x = at.x;
+        transform.translation.y = at.y;
+    }
+}
@@ -0,0 +1,309 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::player::{Player, PLAYER_MAX_HEALTH};
+use crate::waves::WaveCleared;
+use crate::controls::PlayerInput;
+use crate::hud::{AmmoChanged, HealthChanged};
+use crate::weapon::{self, Caliber, Loadout, Weapon};
+
+// Health restored by a medkit
+const MEDKIT_HEAL: i32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Consumable {
+    Medkit,
+}
+
+// Anything that can be handed to the player
+#[derive(Clone, Copy, Debug)]
+pub enum Item {
+    Ammo(Caliber, u32),
+    Weapon(&'static Weapon),
+    Consumable(Consumable, u32),
+}
+
+// Send one of these to give the player something, e.g. from a pickup or a shop
+#[derive(Event)]
+pub struct GrantItem {
+    pub item: Item,
+}
+
+#[derive(Component)]
+pub struct Inventory {
+    // Spare rounds, not counting what is loaded in each gun
+    pub ammo: HashMap<Caliber, u32>,
+    pub loadout: Loadout,
+    pub consumables: HashMap<Consumable, u32>,
+}
+
+impl Default for Inventory {
+    // What the player starts a run with
+    fn default() -> Self {
+        let mut inventory = Inventory {
+            ammo: HashMap::default(),
+            loadout: Loadout::new(&weapon::WEAPONS),
+            consumables: HashMap::default(),
+        };
+        inventory.grant(Item::Ammo(Caliber::NineMil, 120));
+        inventory.grant(Item::Ammo(Caliber::TwelveGauge, 24));
+        inventory.grant(Item::Consumable(Consumable::Medkit, 1));
+        inventory
+    }
+}
+
+impl Inventory {
+    pub fn grant(&mut self, item: Item) {
+        match item {
+            Item::Ammo(caliber, amount) => {
+                *self.ammo.entry(caliber).or_insert(0) += amount;
+            }
+            Item::Weapon(weapon) => {
+                // A gun we already have is still good for its bullets
+                if self.loadout.carries(weapon) {
+                    self.grant(Item::Ammo(weapon.caliber, weapon.magazine));
+                } else {
+                    self.loadout.add(weapon);
+                }
+            }
+            Item::Consumable(consumable, amount) => {
+                *self.consumables.entry(consumable).or_insert(0) += amount;
+            }
+        }
+    }
+
+    pub fn ammo(&self, caliber: Caliber) -> u32 {
+        self.ammo.get(&caliber).copied().unwrap_or(0)
+    }
+
+    // Takes up to amount from the reserves, returning how much there actually was
+    pub fn take_ammo(&mut self, caliber: Caliber, amount: u32) -> u32 {
+        let reserve = self.ammo.entry(caliber).or_insert(0);
+        let taken = amount.min(*reserve);
+        *reserve -= taken;
+        taken
+    }
+
+    pub fn consumables(&self, consumable: Consumable) -> u32 {
+        self.consumables.get(&consumable).copied().unwrap_or(0)
+    }
+
+    // Returns false if there were none left
+    pub fn take_consumable(&mut self, consumable: Consumable) -> bool {
+        match self.consumables.get_mut(&consumable) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Patches the player up, returning their new health. No point wasting a medkit on full health.
+    pub fn use_medkit(&mut self, health: i32) -> Option<i32> {
+        if health >= PLAYER_MAX_HEALTH || !self.take_consumable(Consumable::Medkit) {
+            return None;
+        }
+        Some((health + MEDKIT_HEAL).min(PLAYER_MAX_HEALTH))
+    }
+
+    pub fn start_reload(&mut self) {
+        let reserve = self.ammo(self.loadout.current().weapon.caliber);
+        self.loadout.current_mut().start_reload(reserve);
+    }
+
+    // Top up the magazine from the reserves
+    pub fn finish_reload(&mut self) {
+        let slot = self.loadout.current();
+        let (caliber, wanted) = (slot.weapon.caliber, slot.weapon.magazine - slot.in_magazine);
+        let taken = self.take_ammo(caliber, wanted);
+
+        let slot = self.loadout.current_mut();
+        slot.in_magazine += taken;
+        slot.reloading = 0.0;
+    }
+}
+
+pub fn grant_items(
+    mut grants: EventReader<GrantItem>,
+    mut players: Query<&mut Inventory, With<Player>>,
+    mut ammo: EventWriter<AmmoChanged>,
+){
+    if players.is_empty() || grants.is_empty() {
+        return;
+    }
+    let mut inventory = players.single_mut();
+
+    for grant in grants.iter() {
+        inventory.grant(grant.item);
+    }
+    ammo.send(AmmoChanged::new(&inventory));
+}
+
+// Surviving a wave restocks the player
+pub fn wave_rewards(
+    mut cleared: EventReader<WaveCleared>,
+    mut grants: EventWriter<GrantItem>,
+){
+    for _ in cleared.iter() {
+        grants.send(GrantItem { item: Item::Ammo(Caliber::NineMil, 60) });
+        grants.send(GrantItem { item: Item::Ammo(Caliber::TwelveGauge, 12) });
+        grants.send(GrantItem { item: Item::Consumable(Consumable::Medkit, 1) });
+    }
+}
+
+pub fn use_consumables(
+    input: Res<PlayerInput>,
+    mut players: Query<(&mut Player, &mut Inventory)>,
+    mut health: EventWriter<HealthChanged>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let (mut player, mut inventory) = players.single_mut();
+
+    if !input.use_medkit {
+        return;
+    }
+    if let Some(healed) = inventory.use_medkit(player.health) {
+        player.health = healed;
+        health.send(HealthChanged { health: player.health });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::{PISTOL, SHOTGUN};
+
+    // Just the pistol and shotgun, with nothing in reserve
+    fn inventory() -> Inventory {
+        Inventory {
+            ammo: HashMap::default(),
+            loadout: Loadout::new(&[&PISTOL, &SHOTGUN]),
+            consumables: HashMap::default(),
+        }
+    }
+
+    #[test]
+    fn reload_fills_the_magazine() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Ammo(Caliber::NineMil, 20));
+        inventory.loadout.current_mut().in_magazine = 5;
+
+        inventory.start_reload();
+        assert!(inventory.loadout.current().is_reloading());
+        inventory.finish_reload();
+
+        assert_eq!(inventory.loadout.current().in_magazine, PISTOL.magazine);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 20 - (PISTOL.magazine - 5));
+        assert!(!inventory.loadout.current().is_reloading());
+    }
+
+    #[test]
+    fn partial_reload_takes_what_is_left() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Ammo(Caliber::NineMil, 4));
+        inventory.loadout.current_mut().in_magazine = 5;
+
+        inventory.start_reload();
+        inventory.finish_reload();
+
+        assert_eq!(inventory.loadout.current().in_magazine, 9);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 0);
+    }
+
+    #[test]
+    fn no_reload_from_an_empty_reserve() {
+        let mut inventory = inventory();
+        inventory.loadout.current_mut().in_magazine = 0;
+
+        inventory.start_reload();
+        assert!(!inventory.loadout.current().is_reloading());
+
+        // Even if it's forced through, nothing appears from nowhere
+        inventory.finish_reload();
+        assert_eq!(inventory.loadout.current().in_magazine, 0);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 0);
+    }
+
+    #[test]
+    fn no_reload_with_a_full_magazine() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Ammo(Caliber::NineMil, 20));
+
+        inventory.start_reload();
+        assert!(!inventory.loadout.current().is_reloading());
+    }
+
+    #[test]
+    fn reserves_are_kept_per_caliber() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Ammo(Caliber::NineMil, 20));
+        inventory.loadout.select(1);
+        inventory.loadout.current_mut().in_magazine = 0;
+
+        inventory.start_reload();
+        assert!(!inventory.loadout.current().is_reloading());
+        inventory.grant(Item::Ammo(Caliber::TwelveGauge, 3));
+        inventory.start_reload();
+        inventory.finish_reload();
+
+        assert_eq!(inventory.loadout.current().in_magazine, 3);
+        assert_eq!(inventory.ammo(Caliber::TwelveGauge), 0);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 20);
+    }
+
+    #[test]
+    fn take_ammo_stops_at_zero() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Ammo(Caliber::TwelveGauge, 5));
+        assert_eq!(inventory.take_ammo(Caliber::TwelveGauge, 8), 5);
+        assert_eq!(inventory.take_ammo(Caliber::TwelveGauge, 8), 0);
+        assert_eq!(inventory.take_ammo(Caliber::NineMil, 1), 0);
+    }
+
+    #[test]
+    fn granting_a_carried_weapon_gives_its_ammo() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Weapon(&SHOTGUN));
+        assert_eq!(inventory.loadout.slots.len(), 2);
+        assert_eq!(inventory.ammo(Caliber::TwelveGauge), SHOTGUN.magazine);
+
+        inventory.grant(Item::Weapon(&crate::weapon::SMG));
+        assert_eq!(inventory.loadout.slots.len(), 3);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 0);
+    }
+
+    #[test]
+    fn take_consumable() {
+        let mut inventory = inventory();
+        assert!(!inventory.take_consumable(Consumable::Medkit));
+
+        inventory.grant(Item::Consumable(Consumable::Medkit, 1));
+        assert!(inventory.take_consumable(Consumable::Medkit));
+        assert!(!inventory.take_consumable(Consumable::Medkit));
+        assert_eq!(inventory.consumables(Consumable::Medkit), 0);
+    }
+
+    #[test]
+    fn medkit_is_kept_at_full_health() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Consumable(Consumable::Medkit, 1));
+
+        assert_eq!(inventory.use_medkit(PLAYER_MAX_HEALTH), None);
+        assert_eq!(inventory.consumables(Consumable::Medkit), 1);
+    }
+
+    #[test]
+    fn medkit_heals_up_to_full() {
+        let mut inventory = inventory();
+        inventory.grant(Item::Consumable(Consumable::Medkit, 2));
+
+        assert_eq!(inventory.use_medkit(1), Some((1 + MEDKIT_HEAL).min(PLAYER_MAX_HEALTH)));
+        assert_eq!(inventory.use_medkit(PLAYER_MAX_HEALTH - 1), Some(PLAYER_MAX_HEALTH));
+        assert_eq!(inventory.consumables(Consumable::Medkit), 0);
+        assert_eq!(inventory.use_medkit(1), None);
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub use level_format::{Level, LevelError, Point};
+
+use crate::{GAME_WIDTH, GAME_HEIGHT};
+use crate::game::OnGameScreen;
+use crate::collsion_objects::CollisionObject;
+use crate::utils::assets_dir;
+use crate::player::Player;
+
+// Tiles this close to the player's tile are spawned, and despawned again once they are
+// further away than the unload radius. The gap stops tiles flickering on a boundary.
+const LOAD_RADIUS: i32 = 1;
+const UNLOAD_RADIUS: i32 = 2;
+
+pub fn point_to_vec(point: Point) -> Vec2 {
+    Vec2::new(point.x, point.y)
+}
+
+// World position of the bottom left corner of a tile
+pub fn level_origin(level: &Level) -> Vec2 {
+    Vec2::new(level.x as f32 * GAME_WIDTH, level.y as f32 * GAME_HEIGHT)
+}
+
+pub fn levels_dir() -> PathBuf {
+    assets_dir().join("levels")
+}
+
+// Every tile in the world, and which of them currently have entities spawned. The tiles are
+// kept in order so spawn points and replays come out the same however the files were found.
+#[derive(Resource)]
+pub struct LevelTiles {
+    pub levels: BTreeMap<(i32, i32), Level>,
+    pub loaded: HashSet<(i32, i32)>,
+}
+
+impl LevelTiles {
+    // Read and validate every tile up front, so a broken one stops the game before it starts
+    pub fn load() -> Result<LevelTiles, LevelError> {
+        let dir = levels_dir();
+        let mut levels = Vec::new();
+
+        for (x, y) in level_format::find_tiles(&dir)? {
+            levels.push(Level::load(&dir.join(Level::file_name(x, y)))?);
+        }
+        level_format::check_neighbours(&dir, &levels)?;
+
+        Ok(LevelTiles::from_levels(levels))
+    }
+
+    pub fn from_levels(levels: Vec<Level>) -> LevelTiles {
+        LevelTiles {
+            levels: levels.into_iter().map(|level| ((level.x, level.y), level)).collect(),
+            loaded: HashSet::default(),
+        }
+    }
+
+    pub fn tile_at(loc: Vec2) -> (i32, i32) {
+        ((loc.x / GAME_WIDTH).floor() as i32, (loc.y / GAME_HEIGHT).floor() as i32)
+    }
+
+    pub fn contains(&self, loc: Vec2) -> bool {
+        self.levels.contains_key(&LevelTiles::tile_at(loc))
+    }
+
+    // The smallest rectangle that covers every tile
+    pub fn bounds(&self) -> Rect {
+        let mut tiles = self.levels.keys();
+        let Some(&(first_x, first_y)) = tiles.next() else {
+            return Rect::new(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT);
+        };
+
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (first_x, first_y, first_x, first_y);
+        for &(x, y) in tiles {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+
+        Rect::new(
+            min_x as f32 * GAME_WIDTH,
+            min_y as f32 * GAME_HEIGHT,
+            (max_x + 1) as f32 * GAME_WIDTH,
+            (max_y + 1) as f32 * GAME_HEIGHT,
+        )
+    }
+}
+
+// Marks everything spawned for a tile so it can be cleared when the tile unloads
+#[derive(Component)]
+pub struct TileMember {
+    pub tile: (i32, i32),
+}
+
+pub fn spawn_level(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    level: &Level,
+){
+    let origin = level_origin(level);
+    let tile = (level.x, level.y);
+
+    // Sprites are drawn around their centre, the background fills the whole tile
+    let centre = origin + Vec2::new(GAME_WIDTH, GAME_HEIGHT) / 2.0;
+    let texture_path = Path::new("images").join("scenery").join("street_scene.png");
+    let texture_handle = asset_server.load(texture_path);
+    commands.spawn((
+        SpriteBundle {
+            texture: texture_handle,
+            transform: Transform::from_xyz(centre.x, centre.y, -1.0).with_scale(Vec3::splat(1.0)),
+            ..default()
+        },
+    ))
+    .insert(TileMember{
+        tile: tile
+    })
+    .insert(OnGameScreen);
+
+    for object in level.objects.iter() {
+        let texture_path = Path::new("images").join("objects").join(format!("{}.png", object.description));
+        let texture_handle = asset_server.load(texture_path);
+        let loc = origin + point_to_vec(object.location);
+
+        commands.spawn((
+            SpriteBundle {
+                texture: texture_handle,
+                transform: Transform::from_xyz(loc.x, loc.y, 0.0).with_scale(Vec3::splat(object.scale)),
+                ..default()
+            },
+        ))
+        .insert(CollisionObject{
+            loc: loc,
+            hit_box: point_to_vec(object.hit_box) * object.scale
+        })
+        .insert(TileMember{
+            tile: tile
+        })
+        .insert(OnGameScreen);
+    }
+}
+
+// Spawn the tiles around the player and clear away the ones they have left behind
+pub fn stream_tiles(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut tiles: ResMut<LevelTiles>,
+    players: Query<&Player>,
+    members: Query<(Entity, &TileMember)>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let (player_x, player_y) = LevelTiles::tile_at(players.single().loc);
+
+    let far: Vec<(i32, i32)> = tiles.loaded.iter()
+        .filter(|(x, y)| (x - player_x).abs() > UNLOAD_RADIUS || (y - player_y).abs() > UNLOAD_RADIUS)
+        .copied()
+        .collect();
+    if !far.is_empty() {
+        for (entity, member) in members.iter() {
+            if far.contains(&member.tile) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for tile in far.iter() {
+            tiles.loaded.remove(tile);
+        }
+    }
+
+    for x in (player_x - LOAD_RADIUS)..=(player_x + LOAD_RADIUS) {
+        for y in (player_y - LOAD_RADIUS)..=(player_y + LOAD_RADIUS) {
+            if tiles.loaded.contains(&(x, y)) || !tiles.levels.contains_key(&(x, y)) {
+                continue;
+            }
+            spawn_level(&mut commands, &asset_server, &tiles.levels[&(x, y)]);
+            tiles.loaded.insert((x, y));
+        }
+    }
+}
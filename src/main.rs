@@ -1,5 +1,5 @@
 use bevy::{prelude::*}; 
-use bevy::window::PresentMode;
+use std::path::Path;
 
 mod game;
 mod menu;
@@ -8,6 +8,27 @@ mod utils;
 mod bullet;
 mod blood; 
 mod zombie;
+mod level;
+mod collsion_objects;
+mod navigation;
+mod game_over;
+mod waves;
+mod weapon;
+mod inventory;
+mod camera;
+mod controls;
+mod controls_menu;
+mod pause;
+mod settings;
+mod settings_menu;
+mod sound;
+mod hud;
+mod score;
+mod high_scores_menu;
+mod save;
+mod rng;
+mod replay;
+mod interpolation;
 
 pub const GAME_WIDTH: f32 = 1280.0;
 pub const GAME_HEIGHT: f32 = 720.0;
@@ -19,34 +40,85 @@ pub enum MainGameState {
     #[default]
     Menu,
     Game,
+    // Passed through on the way back into Game to start a fresh run
+    Restarting,
+    GameOver,
 }
 
 #[derive(Resource)]
 pub struct GameDetails {
-    pub width: u32,
-    pub height: u32,
-    pub offset_x: f32,
-    pub offset_y: f32,
+    // Edges of the world in world coordinates, covering every level tile
+    pub bounds: Rect,
+}
+
+// The value given after a flag, if the flag is there
+fn arg_value(flag: &str) -> Result<Option<String>, String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    if args.next().is_none() {
+        return Ok(None);
+    }
+    args.next().map(Some).ok_or_else(|| format!("{} needs a value after it", flag))
+}
+
+fn seed_arg() -> Result<Option<u64>, String> {
+    match arg_value("--seed")? {
+        Some(seed) => seed.parse().map(Some).map_err(|_| format!("--seed needs a whole number, not {}", seed)),
+        None => Ok(None),
+    }
+}
+
+fn replay_arg() -> Result<Option<replay::Replay>, String> {
+    match arg_value("--replay")? {
+        Some(path) => replay::Replay::load(Path::new(&path)).map(Some).map_err(|err| err.to_string()),
+        None => Ok(None),
+    }
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "Zombie Game".to_string(),
-                resolution: (GAME_WIDTH, GAME_HEIGHT).into(),
-                present_mode: PresentMode::AutoVsync,
-                ..default()
-            }),
-            ..default()
-        }))
+    // Loaded up front so the window opens the way the player left it
+    let settings = settings::Settings::load();
+    // For machines without a sound card, e.g. running headless
+    let audio = !std::env::args().any(|arg| arg == "--no-audio");
+    // Play every run from the same seed with --seed <number>, or watch one back with --replay <file>
+    let (seed, playback) = match (seed_arg(), replay_arg()) {
+        (Ok(seed), Ok(playback)) => (seed, playback),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut plugins = DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(settings.window()),
+        ..default()
+    });
+    if !audio {
+        plugins = plugins.disable::<bevy::audio::AudioPlugin>();
+    }
+
+    let mut app = App::new();
+    app
+        .add_plugins(plugins)
         .add_state::<MainGameState>()
-        .insert_resource(GameDetails{width: 3, height: 3,offset_x:0.0,offset_y:0.0})
+        .insert_resource(GameDetails{bounds: Rect::new(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT)})
+        .insert_resource(controls::InputMap::load())
+        .insert_resource(settings)
+        .insert_resource(rng::GameRng::new(seed))
+        .add_systems(Update, settings::apply_window_settings.run_if(resource_changed::<settings::Settings>()))
         .add_plugins((
             menu::MenuPlugin,
-            game::GamePlugin
-        ))
-        .run();
+            settings_menu::SettingsMenuPlugin,
+            controls_menu::ControlsMenuPlugin,
+            high_scores_menu::HighScoresMenuPlugin,
+            game::GamePlugin,
+            pause::PausePlugin,
+            sound::SoundPlugin { enabled: audio },
+            game_over::GameOverPlugin
+        ));
+    if let Some(playback) = playback {
+        app.insert_resource(replay::ReplayPlayback::new(playback));
+    }
+    app.run();
 }
 
 // Generic system that takes a component as a parameter, and will despawn all entities with that component
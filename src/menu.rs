@@ -2,11 +2,15 @@ use bevy::{app::AppExit, prelude::*};
 use std::path::Path;
 
 use super::{despawn_screen,MainGameState};
+use crate::save::{PendingLoad, SaveGame};
 
 // State used for the current menu screen
 #[derive(Clone, Eq, PartialEq, Debug, Hash, States, Default)]
 pub enum MenuState {
     Main,
+    Settings,
+    Controls,
+    HighScores,
     #[default]
     Disabled,
 }
@@ -17,15 +21,14 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_state::<MenuState>()
-            .add_systems(
-                OnEnter(MainGameState::Menu),menu_setup,
-                // despawn_screen::<OnMainMenuScreen>.in_schedule(OnExit(MainGameState::Menu)),
-            )
+            .add_systems(OnEnter(MainGameState::Menu), menu_setup)
+            .add_systems(OnEnter(MenuState::Main), main_menu_setup)
+            .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
             .add_systems(
                 Update,
                 (menu_action,button_system).run_if(in_state(MainGameState::Menu)),
             )
-            .add_systems(OnExit(MainGameState::Menu), despawn_screen::<OnMainMenuScreen>);
+            .add_systems(OnExit(MainGameState::Menu), despawn_screen::<OnMenuScreen>);
     }
 }
 
@@ -37,36 +40,51 @@ pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 pub const HOVERED_PRESSED_BUTTON: Color = Color::rgb(0.25, 0.65, 0.25);
 pub const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
+// Everything that stays up while moving between menu pages, like the camera
+#[derive(Component)]
+pub struct OnMenuScreen;
+
 #[derive(Component)]
 pub struct OnMainMenuScreen;
 
+// Tells the player why a saved run couldn't be continued
+#[derive(Component)]
+struct MenuMessage;
+
 // Tag component used to mark which setting is currently selected
 #[derive(Component)]
-struct SelectedOption;
+pub struct SelectedOption;
 
 #[derive(Component)]
-enum MenuButtonAction {
+pub enum MenuButtonAction {
+    Continue,
     Play,
+    Settings,
+    HighScores,
     Quit,
 }
 
 fn menu_setup(
     mut commands: Commands,
-    _meshes: ResMut<Assets<Mesh>>,
-    _materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
+    mut menu_state: ResMut<NextState<MenuState>>,
 ){
     commands
         .spawn(Camera2dBundle::default())
-        .insert(OnMainMenuScreen);
-    
+        .insert(OnMenuScreen);
+    menu_state.set(MenuState::Main);
+}
+
+fn main_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
     let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
     let font = asset_server.load(font_path);
 
     let button_style = Style {
         width: Val::Px(250.0),
         height: Val::Px(65.0),
-        margin: UiRect::all(Val::Px(20.0)),
+        margin: UiRect::all(Val::Px(12.0)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..default()
@@ -101,11 +119,42 @@ fn menu_setup(
                     },
                 )
                 .with_style(Style {
-                    margin: UiRect::all(Val::Px(50.0)),
+                    margin: UiRect::all(Val::Px(30.0)),
                     ..default()
                 }),
             );
 
+            parent
+                .spawn(
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: TEXT_COLOR,
+                        },
+                    )
+                    .with_style(Style {
+                        max_width: Val::Px(600.0),
+                        margin: UiRect::horizontal(Val::Px(20.0)),
+                        ..default()
+                    }),
+                )
+                .insert(MenuMessage);
+
+            if SaveGame::exists() {
+                parent
+                    .spawn(ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(MenuButtonAction::Continue)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("Continue", button_text_style.clone()));
+                    });
+            }
+
             parent
                 .spawn(ButtonBundle {
                     style: button_style.clone(),
@@ -117,6 +166,28 @@ fn menu_setup(
                     parent.spawn(TextBundle::from_section("Play", button_text_style.clone()));
                 });
 
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(MenuButtonAction::Settings)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Settings", button_text_style.clone()));
+                });
+
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                })
+                .insert(MenuButtonAction::HighScores)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("High Scores", button_text_style.clone()));
+                });
+
             parent
                 .spawn(ButtonBundle {
                     style: button_style.clone(),
@@ -131,7 +202,7 @@ fn menu_setup(
 }
 
 // This system handles changing all buttons color based on mouse interaction
-fn button_system(
+pub fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor, Option<&SelectedOption>),
         (Changed<Interaction>, With<Button>),
@@ -155,15 +226,32 @@ fn menu_action(
     mut app_exit_events: EventWriter<AppExit>,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut game_state: ResMut<NextState<MainGameState>>,
+    mut commands: Commands,
+    mut messages: Query<&mut Text, With<MenuMessage>>,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
+                MenuButtonAction::Continue => match SaveGame::load() {
+                    // The file stays until the run is back in play, see game_setup
+                    Ok(save) => {
+                        commands.insert_resource(PendingLoad(save));
+                        game_state.set(MainGameState::Game);
+                        menu_state.set(MenuState::Disabled);
+                    }
+                    Err(err) => {
+                        for mut text in messages.iter_mut() {
+                            text.sections[0].value = format!("Couldn't continue: {}", err);
+                        }
+                    }
+                },
                 MenuButtonAction::Quit => app_exit_events.send(AppExit),
                 MenuButtonAction::Play => {
                     game_state.set(MainGameState::Game);
                     menu_state.set(MenuState::Disabled);
                 }
+                MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::HighScores => menu_state.set(MenuState::HighScores),
             }
         }
     }
@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::level::{LevelTiles, level_origin, point_to_vec};
+
+// Size of a grid square, small enough to find the gaps between scenery
+const CELL_SIZE: f32 = 40.0;
+// Keep paths this far from scenery so a zombie's hit box fits through (half the zombie hit box)
+const CLEARANCE: f32 = 50.0;
+
+// Movement costs, scaled up so we can stay in whole numbers
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Walkable squares across the whole world, built once from the level tiles
+#[derive(Resource)]
+pub struct NavGrid {
+    origin: Vec2,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+}
+
+impl NavGrid {
+    pub fn new(tiles: &LevelTiles) -> Self {
+        let bounds = tiles.bounds();
+        let width = (bounds.width() / CELL_SIZE).ceil() as i32;
+        let height = (bounds.height() / CELL_SIZE).ceil() as i32;
+
+        // Every object's hit box, grown by the clearance
+        let mut obstacles = Vec::<Rect>::new();
+        for level in tiles.levels.values() {
+            let origin = level_origin(level);
+            for object in level.objects.iter() {
+                let centre = origin + point_to_vec(object.location);
+                let half = point_to_vec(object.hit_box) * object.scale / 2.0 + Vec2::splat(CLEARANCE);
+                obstacles.push(Rect::from_center_half_size(centre, half));
+            }
+        }
+
+        let mut grid = NavGrid {
+            origin: bounds.min,
+            width: width,
+            height: height,
+            blocked: vec![false; (width * height) as usize],
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let centre = grid.cell_centre((x, y));
+                let blocked = !tiles.contains(centre)
+                    || obstacles.iter().any(|obstacle| obstacle.contains(centre));
+                grid.blocked[(y * width + x) as usize] = blocked;
+            }
+        }
+
+        grid
+    }
+
+    fn cell_at(&self, loc: Vec2) -> (i32, i32) {
+        let cell = ((loc - self.origin) / CELL_SIZE).floor();
+        (
+            (cell.x as i32).clamp(0, self.width - 1),
+            (cell.y as i32).clamp(0, self.height - 1),
+        )
+    }
+
+    fn cell_centre(&self, (x, y): (i32, i32)) -> Vec2 {
+        self.origin + Vec2::new(x as f32 + 0.5, y as f32 + 0.5) * CELL_SIZE
+    }
+
+    fn index(&self, (x, y): (i32, i32)) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    fn is_open(&self, (x, y): (i32, i32)) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height && !self.blocked[self.index((x, y))]
+    }
+
+    // The closest walkable square, for when a point is inside (or too near) some scenery
+    fn nearest_open(&self, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+        if self.is_open((x, y)) {
+            return Some((x, y));
+        }
+        for ring in 1..self.width.max(self.height) {
+            let mut best: Option<((i32, i32), i32)> = None;
+            for dy in -ring..=ring {
+                for dx in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    let cell = (x + dx, y + dy);
+                    let distance = dx * dx + dy * dy;
+                    if self.is_open(cell) && best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                        best = Some((cell, distance));
+                    }
+                }
+            }
+            if let Some((cell, _)) = best {
+                return Some(cell);
+            }
+        }
+        None
+    }
+
+    // Octile distance, which matches the cost of moving on an eight way grid
+    fn heuristic((ax, ay): (i32, i32), (bx, by): (i32, i32)) -> u32 {
+        let dx = (ax - bx).unsigned_abs();
+        let dy = (ay - by).unsigned_abs();
+        STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+    }
+
+    // A* from start to goal, returning the points to walk through (ending at the goal).
+    // Points inside scenery are moved to the nearest walkable square.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let start_cell = self.nearest_open(self.cell_at(start))?;
+        let goal_cell = self.nearest_open(self.cell_at(goal))?;
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[self.index(start_cell)] = 0;
+        open.push(Reverse((NavGrid::heuristic(start_cell, goal_cell), self.index(start_cell))));
+
+        while let Some(Reverse((_, current))) = open.pop() {
+            let cell = (current as i32 % self.width, current as i32 / self.width);
+            if cell == goal_cell {
+                break;
+            }
+
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if dx == 0 && dy == 0 {
+                        continue;
+                    }
+                    let next = (cell.0 + dx, cell.1 + dy);
+                    if !self.is_open(next) {
+                        continue;
+                    }
+                    let diagonal = dx != 0 && dy != 0;
+                    // Don't cut across the corner of something
+                    if diagonal && (!self.is_open((cell.0 + dx, cell.1)) || !self.is_open((cell.0, cell.1 + dy))) {
+                        continue;
+                    }
+
+                    let next_cost = cost[current] + if diagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+                    let next_index = self.index(next);
+                    if next_cost < cost[next_index] {
+                        cost[next_index] = next_cost;
+                        came_from[next_index] = current;
+                        open.push(Reverse((next_cost + NavGrid::heuristic(next, goal_cell), next_index)));
+                    }
+                }
+            }
+        }
+
+        let goal_index = self.index(goal_cell);
+        if cost[goal_index] == u32::MAX {
+            return None;
+        }
+
+        // Walk back from the goal, keeping only the squares where the path turns
+        let mut cells = vec![goal_index];
+        while *cells.last().unwrap() != self.index(start_cell) {
+            cells.push(came_from[*cells.last().unwrap()]);
+        }
+        cells.reverse();
+
+        let mut path = Vec::<Vec2>::new();
+        for (position, index) in cells.iter().enumerate() {
+            let cell = (*index as i32 % self.width, *index as i32 / self.width);
+            if position > 0 && position + 1 < cells.len() {
+                let previous = cells[position - 1] as i32;
+                let following = cells[position + 1] as i32;
+                if *index as i32 - previous == following - *index as i32 {
+                    continue;
+                }
+            }
+            path.push(self.cell_centre(cell));
+        }
+
+        // Already standing in the first square, so no need to walk to its centre
+        if path.len() > 1 && start_cell == self.cell_at(start) {
+            path.remove(0);
+        }
+
+        // Finish exactly on the goal when it is somewhere we can stand
+        if goal_cell == self.cell_at(goal) {
+            if let Some(last) = path.last_mut() {
+                *last = goal;
+            }
+        }
+
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collsion_objects::CollisionObject;
+    use crate::level::{Level, Point};
+    use level_format::LevelObject;
+
+    // A single tile with a wall standing in the middle of it
+    fn grid_with_wall(location: Vec2, hit_box: Vec2) -> (NavGrid, CollisionObject) {
+        let mut level = Level::new(0, 0, "Test tile");
+        level.objects.push(LevelObject {
+            description: "blockade".to_string(),
+            location: Point::new(location.x, location.y),
+            hit_box: Point::new(hit_box.x, hit_box.y),
+            scale: 1.0,
+        });
+        let grid = NavGrid::new(&LevelTiles::from_levels(vec![level]));
+        (grid, CollisionObject { loc: location, hit_box: hit_box })
+    }
+
+    fn walkable(grid: &NavGrid, loc: Vec2) -> bool {
+        grid.is_open(grid.cell_at(loc))
+    }
+
+    #[test]
+    fn straight_path() {
+        let grid = NavGrid::new(&LevelTiles::from_levels(vec![Level::new(0, 0, "Empty tile")]));
+        let goal = Vec2::new(600.0, 100.0);
+        assert_eq!(grid.find_path(Vec2::new(100.0, 100.0), goal), Some(vec![goal]));
+    }
+
+    #[test]
+    fn path_around_obstacle() {
+        // Leaves a gap along the bottom of the tile
+        let (grid, wall) = grid_with_wall(Vec2::new(640.0, 460.0), Vec2::new(40.0, 400.0));
+        let start = Vec2::new(300.0, 500.0);
+        let goal = Vec2::new(1000.0, 500.0);
+
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().any(|point| point.y < wall.loc.y - wall.hit_box.y / 2.0));
+
+        let mut from = start;
+        for point in path {
+            assert!(walkable(&grid, point), "{} is in the wall", point);
+            assert!(!wall.blocks_line(from, point), "{} to {} goes through the wall", from, point);
+            from = point;
+        }
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        // Floor to ceiling, there's no way past
+        let (grid, _) = grid_with_wall(Vec2::new(640.0, 360.0), Vec2::new(40.0, 720.0));
+        assert_eq!(grid.find_path(Vec2::new(300.0, 500.0), Vec2::new(1000.0, 500.0)), None);
+    }
+
+    #[test]
+    fn start_inside_obstacle() {
+        let (grid, wall) = grid_with_wall(Vec2::new(640.0, 460.0), Vec2::new(40.0, 400.0));
+        let goal = Vec2::new(1000.0, 500.0);
+
+        let path = grid.find_path(wall.loc, goal).unwrap();
+        assert!(walkable(&grid, path[0]), "{} is in the wall", path[0]);
+        assert_eq!(path.last(), Some(&goal));
+    }
+
+    #[test]
+    fn goal_inside_obstacle() {
+        let (grid, wall) = grid_with_wall(Vec2::new(640.0, 460.0), Vec2::new(40.0, 400.0));
+
+        // Gets as close as it can rather than giving up
+        let path = grid.find_path(Vec2::new(300.0, 500.0), wall.loc).unwrap();
+        let end = *path.last().unwrap();
+        assert_ne!(end, wall.loc);
+        assert!(walkable(&grid, end), "{} is in the wall", end);
+        assert!(end.distance(wall.loc) <= wall.hit_box.x / 2.0 + CLEARANCE + CELL_SIZE);
+    }
+}
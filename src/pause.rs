@@ -0,0 +1,232 @@
+use bevy::prelude::*;
+use std::path::Path;
+
+use super::{despawn_screen, MainGameState};
+use crate::controls::PlayerInput;
+use crate::menu::{self, MenuState, TEXT_COLOR, NORMAL_BUTTON};
+use crate::save::{self, SaveMessage, SaveRun};
+
+// Whether the run is going or stopped under the pause overlay, only meaningful in MainGameState::Game
+#[derive(Clone, Eq, PartialEq, Debug, Hash, States, Default)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+    ConfirmQuit,
+    Settings,
+    Controls,
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_state::<PauseState>()
+            .add_event::<SaveRun>()
+            .add_systems(OnExit(PauseState::Running), pause_time)
+            .add_systems(OnEnter(PauseState::Running), resume_time)
+            .add_systems(OnEnter(PauseState::Paused), pause_menu_setup)
+            .add_systems(OnExit(PauseState::Paused), despawn_screen::<OnPauseScreen>)
+            .add_systems(OnEnter(PauseState::ConfirmQuit), confirm_quit_setup)
+            .add_systems(OnExit(PauseState::ConfirmQuit), despawn_screen::<OnConfirmQuitScreen>)
+            .add_systems(OnExit(MainGameState::Game), reset_pause)
+            .add_systems(OnEnter(MainGameState::Restarting), restart)
+            .add_systems(
+                Update,
+                (
+                    pause_toggle,
+                    pause_action,
+                    // Before the state changes and the run is torn down
+                    save::save_run.after(pause_action),
+                    menu::button_system,
+                ).run_if(in_state(MainGameState::Game)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnPauseScreen;
+
+#[derive(Component)]
+struct OnConfirmQuitScreen;
+
+#[derive(Component)]
+enum PauseButtonAction {
+    Resume,
+    Restart,
+    Settings,
+    SaveAndQuit,
+    Quit,
+    ConfirmQuit,
+    CancelQuit,
+}
+
+// Dims the game behind the overlay
+const OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+
+fn pause_time(mut time: ResMut<Time>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time>) {
+    time.unpause();
+}
+
+// Leaving the game always leaves it unpaused, ready for the next run
+fn reset_pause(mut pause_state: ResMut<NextState<PauseState>>) {
+    pause_state.set(PauseState::Running);
+}
+
+// Passing through Restarting tears the run down and Game builds a fresh one
+fn restart(mut game_state: ResMut<NextState<MainGameState>>) {
+    game_state.set(MainGameState::Game);
+}
+
+fn pause_toggle(
+    input: Res<PlayerInput>,
+    state: Res<State<PauseState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+){
+    if !input.pause {
+        return;
+    }
+    match state.get() {
+        PauseState::Running => pause_state.set(PauseState::Paused),
+        PauseState::Paused => pause_state.set(PauseState::Running),
+        PauseState::ConfirmQuit => pause_state.set(PauseState::Paused),
+        // The settings and controls screens use Escape themselves
+        PauseState::Settings | PauseState::Controls => {}
+    }
+}
+
+fn spawn_overlay<T: Component>(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    marker: T,
+    title: &str,
+    buttons: Vec<(PauseButtonAction, &str)>,
+){
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(65.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 40.0,
+        color: TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: OVERLAY_COLOR.into(),
+            ..default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 60.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(30.0)),
+                    ..default()
+                }),
+            );
+
+            for (action, label) in buttons {
+                parent
+                    .spawn(ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    })
+                    .insert(action)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, button_text_style.clone()));
+                    });
+            }
+
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 26.0,
+                        color: TEXT_COLOR,
+                    },
+                ))
+                .insert(SaveMessage);
+        });
+}
+
+fn pause_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    spawn_overlay(&mut commands, &asset_server, OnPauseScreen, "Paused", vec![
+        (PauseButtonAction::Resume, "Resume"),
+        (PauseButtonAction::Restart, "Restart"),
+        (PauseButtonAction::Settings, "Settings"),
+        (PauseButtonAction::SaveAndQuit, "Save & quit"),
+        (PauseButtonAction::Quit, "Quit to menu"),
+    ]);
+}
+
+fn confirm_quit_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    spawn_overlay(&mut commands, &asset_server, OnConfirmQuitScreen, "Quit without saving?", vec![
+        (PauseButtonAction::ConfirmQuit, "Quit"),
+        (PauseButtonAction::CancelQuit, "Keep playing"),
+    ]);
+}
+
+fn pause_action(
+    interaction_query: Query<
+        (&Interaction, &PauseButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+    mut save_run: EventWriter<SaveRun>,
+){
+    for (interaction, pause_button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match pause_button_action {
+                PauseButtonAction::Resume => pause_state.set(PauseState::Running),
+                PauseButtonAction::Restart => game_state.set(MainGameState::Restarting),
+                PauseButtonAction::Settings => pause_state.set(PauseState::Settings),
+                PauseButtonAction::SaveAndQuit => save_run.send(SaveRun),
+                PauseButtonAction::Quit => pause_state.set(PauseState::ConfirmQuit),
+                PauseButtonAction::ConfirmQuit => {
+                    game_state.set(MainGameState::Menu);
+                    menu_state.set(MenuState::Main);
+                }
+                PauseButtonAction::CancelQuit => pause_state.set(PauseState::Paused),
+            }
+        }
+    }
+}
@@ -1,25 +1,74 @@
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
 use std::path::Path;
 
-use super::GameDetails;
+use super::{GameDetails,MainGameState};
 
-use crate::{GAME_WIDTH,GAME_HEIGHT,BUFFER_WIDTH,BUFFER_HEIGHT};
+use crate::{BUFFER_WIDTH,BUFFER_HEIGHT};
 use crate::game::*;
 use crate::utils::*;
 use crate::bullet::*;
+use crate::collsion_objects::CollisionObject;
+use crate::level::LevelTiles;
+use crate::inventory::Inventory;
+use crate::camera::GameCamera;
+use crate::controls::{ActiveDevice, PlayerInput};
+use crate::settings::Settings;
+use crate::sound::{PlaySound, Sound};
+use crate::hud::AmmoChanged;
+use crate::rng::GameRng;
+use crate::replay::ReplayPlayback;
+use crate::interpolation::Interpolated;
 
 #[derive(Component)]
 pub struct Player {
-    loc: Vec2,
-    mouse: Vec2,
-    _hit_box: Vec2
+    pub loc: Vec2,
+    // Where the player is aiming in the world
+    pub mouse: Vec2,
+    // Which way the right stick last pointed, kept when it is let go
+    stick_aim: Vec2,
+    pub hit_box: Vec2,
+    pub health: i32,
+    // Seconds left before the player can be hurt again
+    pub invulnerable: f32,
+}
+
+pub const PLAYER_MAX_HEALTH: i32 = 10;
+const PLAYER_INVULNERABLE_TIME: f32 = 1.0;
+// How many times a second the sprite flickers while invulnerable
+const PLAYER_FLASH_RATE: f32 = 10.0;
+
+impl Default for Player {
+    // Where and how the player starts a run
+    fn default() -> Self {
+        Player{
+            loc: Vec2::new(100.0,100.0),
+            mouse: Vec2::new(0.0,0.0),
+            stick_aim: Vec2::new(0.0,1.0),
+            hit_box: Vec2::new(150.0,150.0),
+            health: PLAYER_MAX_HEALTH,
+            invulnerable: 0.0,
+        }
+    }
+}
+
+impl Player {
+    // Returns false if the hit was shrugged off during invulnerability
+    pub fn take_damage(&mut self, damage: i32) -> bool {
+        if self.invulnerable > 0.0 {
+            return false;
+        }
+        self.health -= damage;
+        self.invulnerable = PLAYER_INVULNERABLE_TIME;
+        true
+    }
 }
 
 pub fn create_player( 
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-){
+) -> Entity {
     let texture_path = Path::new("images").join("player").join("player.png");
     let texture_handle = asset_server.load(texture_path);
     let texture_atlas =
@@ -37,86 +86,85 @@ pub fn create_player(
         animation_indices,
         AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
     ))
-    .insert(Player{
-        loc: Vec2::new(100.0,100.0),
-        mouse: Vec2::new(0.0,0.0),
-        _hit_box: Vec2::new(150.0,150.0)
-    })
-    .insert(OnGameScreen);
+    .insert(Interpolated::new(Player::default().loc))
+    .insert(Player::default())
+    .insert(Inventory::default())
+    .insert(OnGameScreen)
+    .id()
 }
 
 const PLAYER_MOVE_SPEED: f32 = 150.0;
+const STICK_AIM_DISTANCE: f32 = 200.0;
+
+// The game only goes on while the player is alive, a tick can't run on after the one they died in
+pub fn player_alive(players: Query<&Player>) -> bool {
+    players.iter().any(|player| player.health > 0)
+}
+
+pub fn player_health(
+    time: Res<FixedTime>,
+    mut players: Query<(&mut Player, &mut TextureAtlasSprite)>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let (mut player, mut sprite) = players.single_mut();
+
+    // Flicker while we can't be hurt
+    if player.invulnerable > 0.0 {
+        player.invulnerable -= time.period.as_secs_f32();
+        let visible = (player.invulnerable * PLAYER_FLASH_RATE) as i32 % 2 == 0;
+        sprite.color.set_a(if visible { 1.0 } else { 0.3 });
+    } else {
+        sprite.color.set_a(1.0);
+    }
+
+    if player.health <= 0 {
+        game_state.set(MainGameState::GameOver);
+    }
+}
 
 pub fn player_mover(
-    time: Res<Time>,
+    time: Res<FixedTime>,
     mut players: Query<(
         &mut Player,
         &mut Transform,
+        &mut Interpolated,
     )>,
-    keys: Res<Input<KeyCode>>,
-    mut game_details: ResMut<GameDetails>
+    input: Res<PlayerInput>,
+    game_details: Res<GameDetails>,
+    objects: Query<&CollisionObject>,
+    tiles: Res<LevelTiles>,
 ){
     if players.is_empty() {
         return;
     }
     
-    let (mut player, mut transform) = players.single_mut();
-
-    if keys.pressed(KeyCode::W){
-        player.loc.y += PLAYER_MOVE_SPEED * time.delta_seconds();
-    }
-    if keys.pressed(KeyCode::S){
-        player.loc.y -= PLAYER_MOVE_SPEED * time.delta_seconds();
-    }
-    if keys.pressed(KeyCode::A){
-        player.loc.x -= PLAYER_MOVE_SPEED * time.delta_seconds();
-    }
-    if keys.pressed(KeyCode::D){
-        player.loc.x += PLAYER_MOVE_SPEED * time.delta_seconds();
-    }
+    let (mut player, mut transform, mut interpolated) = players.single_mut();
 
-    // Don't go out of bounds
-    if player.loc.x < BUFFER_WIDTH {
-        player.loc.x = BUFFER_WIDTH;
-    }
-    if player.loc.y < BUFFER_HEIGHT {
-        player.loc.y = BUFFER_HEIGHT;
-    }
+    // A stick part way over walks rather than runs
+    let next_move = input.movement * PLAYER_MOVE_SPEED * time.period.as_secs_f32();
+    let next_loc = check_position_move(player.loc, next_move, player.hit_box, &objects);
 
-    let max_x = game_details.width as f32 * GAME_WIDTH;
-    if player.loc.x <= GAME_WIDTH / 2.0 {
-        transform.translation.x = player.loc.x - GAME_WIDTH/2.0;
-        game_details.offset_x = 0.0;
-    } else if player.loc.x >= max_x - (GAME_WIDTH/2.0) {
-        transform.translation.x = player.loc.x - (max_x - (GAME_WIDTH/2.0));
-        game_details.offset_x = max_x - GAME_WIDTH;
-    } else {
-        transform.translation.x = 0.0;
-        game_details.offset_x = player.loc.x - GAME_WIDTH/2.0;
+    // Gaps in the world where there is no tile are as solid as the edges
+    if tiles.contains(next_loc) {
+        player.loc = next_loc;
+    } else if tiles.contains(Vec2::new(next_loc.x, player.loc.y)) {
+        player.loc.x = next_loc.x;
+    } else if tiles.contains(Vec2::new(player.loc.x, next_loc.y)) {
+        player.loc.y = next_loc.y;
     }
 
-    if player.loc.x >= max_x - BUFFER_WIDTH{
-        player.loc.x = max_x - BUFFER_WIDTH;
-    }
+    // Don't go out of bounds
+    let bounds = game_details.bounds;
+    player.loc.x = player.loc.x.clamp(bounds.min.x + BUFFER_WIDTH, bounds.max.x - BUFFER_WIDTH);
+    player.loc.y = player.loc.y.clamp(bounds.min.y + BUFFER_HEIGHT, bounds.max.y - BUFFER_HEIGHT);
 
-    let max_y = game_details.height as f32 * GAME_HEIGHT;
-    if player.loc.y <= GAME_HEIGHT / 2.0 {
-        transform.translation.y = player.loc.y - GAME_HEIGHT/2.0;    
-        game_details.offset_y = 0.0;
-    } else if player.loc.y >= max_y - (GAME_HEIGHT/2.0) {
-        transform.translation.y = player.loc.y - (max_y - (GAME_HEIGHT/2.0));
-        game_details.offset_y = max_y - GAME_HEIGHT;
-    } else {
-        transform.translation.y = 0.0;
-        game_details.offset_y = player.loc.y - GAME_HEIGHT/2.0;
-    }
+    interpolated.current = player.loc;
 
-    if player.loc.y > max_y - BUFFER_HEIGHT {
-        player.loc.y = max_y - BUFFER_HEIGHT;
-    }
-    
     // Rotate to face the mouse cursor
-    let direction = player.mouse - transform.translation.truncate(); 
+    let direction = player.mouse - player.loc;
     let angle_to_target =  normalize_angle(
         direction.y.atan2(direction.x)
     );
@@ -125,64 +173,106 @@ pub fn player_mover(
 }
 
 pub fn track_mouse(
-    mut motion_evr: EventReader<CursorMoved>,
-    mut players: Query<&mut Player>
+    device: Res<ActiveDevice>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut players: Query<&mut Player>,
+    cameras: Query<(&Camera, &Transform), With<GameCamera>>,
 ){
-    if players.is_empty(){
+    if *device != ActiveDevice::KeyboardMouse || players.is_empty() || windows.is_empty() || cameras.is_empty() {
         return;
     }
     let mut player = players.single_mut();
+    let (camera, camera_transform) = cameras.single();
 
-    for ev in motion_evr.iter() {
-        player.mouse.x = ev.position.x - (GAME_WIDTH/2.0);
-        player.mouse.y = -1.0 * (ev.position.y - (GAME_HEIGHT/2.0));
-        // println!("Standing at: {:?}, Pointing at: {:?}", player.loc, player.mouse);
+    // Keep the last aim while the cursor is outside the window
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+
+    // The camera might only draw to part of the window
+    let viewport_min = camera.logical_viewport_rect().map_or(Vec2::ZERO, |rect| rect.min);
+
+    // Go from the Transform, the GlobalTransform won't catch up with camera_follow until the end of the frame
+    if let Some(aim) = camera.viewport_to_world_2d(&GlobalTransform::from(*camera_transform), cursor - viewport_min) {
+        player.mouse = aim;
     }
 }
 
+pub fn track_stick(
+    device: Res<ActiveDevice>,
+    input: Res<PlayerInput>,
+    mut players: Query<&mut Player>,
+){
+    if !matches!(*device, ActiveDevice::Gamepad(_)) || players.is_empty() {
+        return;
+    }
+    let mut player = players.single_mut();
+
+    if let Some(aim) = input.aim {
+        player.stick_aim = aim;
+    }
+    // Aim at a point out in front, so the rest of the game can treat it like the mouse
+    player.mouse = player.loc + player.stick_aim * STICK_AIM_DISTANCE;
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn fire_controller(
     mut commands: Commands,
-    buttons: Res<Input<MouseButton>>,
+    input: Res<PlayerInput>,
+    settings: Res<Settings>,
     asset_server: Res<AssetServer>,
-    players: Query<(&Player, &Transform)>,
-    game_details: Res<GameDetails>,
+    mut players: Query<(&Player, &mut Inventory)>,
+    mut sounds: EventWriter<PlaySound>,
+    mut ammo: EventWriter<AmmoChanged>,
+    mut rng: ResMut<GameRng>,
+    playback: Option<Res<ReplayPlayback>>,
 ){
     if players.is_empty() {
         return;
     }
 
-    let (player,transform) = players.single();
+    let (player, mut inventory) = players.single_mut();
+    let loadout = &mut inventory.loadout;
 
-    if buttons.just_pressed(MouseButton::Left) {
-        // Get player location and spawn a new bullet
-        let direction = transform.translation.truncate() - player.mouse; 
-        let angle_to_target =  normalize_angle(
-            direction.y.atan2(direction.x)
-        );
+    let trigger = if loadout.current().weapon.automatic {
+        input.fire
+    } else {
+        input.fire_pressed
+    };
+    if !trigger || loadout.cooldown > 0.0 || loadout.current().is_reloading() {
+        return;
+    }
 
-        let texture_path = Path::new("images").join("objects").join("bullet.png");
-        let texture_handle = asset_server.load(texture_path);
-    
-        // println!("Fire.... pos: {:?}, angle: {:?}",player.loc, angle_to_target);
-
-        commands.spawn((
-                SpriteBundle {
-                    texture: texture_handle,
-                    transform: Transform::from_xyz(
-                        player.loc.x-game_details.offset_x - (GAME_WIDTH/2.0), 
-                        player.loc.y-game_details.offset_y - (GAME_HEIGHT/2.0)
-                        , 2.0
-                    ).with_scale(Vec3::splat(1.0)),
-                    ..default()
-                },
-            )) 
-            .insert(Bullet{
-                loc: Vec2::new(player.loc.x, player.loc.y),
-                angle: angle_to_target - (std::f32::consts::PI/2.0),
-                hit_box: Vec2::new(10.0, 20.0),
-                damage: 1
-            })
-            .insert(OnGameScreen);
-
-    }
-}
\ No newline at end of file
+    // Out of bullets, so pulling the trigger starts a reload instead
+    if loadout.current().in_magazine == 0 {
+        inventory.start_reload();
+        ammo.send(AmmoChanged::new(&inventory));
+        return;
+    }
+
+    let slot = loadout.current_mut();
+    slot.in_magazine -= 1;
+    let weapon = slot.weapon;
+    loadout.cooldown = 1.0 / weapon.fire_rate;
+    sounds.send(PlaySound::at(Sound::Shot, player.loc));
+
+    // Get player location and spawn the bullets
+    let direction = player.loc - player.mouse;
+    let angle_to_target =  normalize_angle(
+        direction.y.atan2(direction.x)
+    );
+
+    let texture_handle = bullet_texture(&asset_server);
+
+    for angle in loadout.current().pellet_angles(angle_to_target - (std::f32::consts::PI/2.0), rng.gameplay()) {
+        spawn_bullet(&mut commands, texture_handle.clone(), Bullet::new(player.loc, angle, weapon));
+    }
+
+    // Don't wait for the next pull of the trigger to notice it's empty.
+    // A replay goes by the setting it was recorded with.
+    let auto_reload = playback.map_or(settings.auto_reload, |playback| playback.auto_reload);
+    if auto_reload && loadout.current().in_magazine == 0 {
+        inventory.start_reload();
+    }
+    ammo.send(AmmoChanged::new(&inventory));
+}
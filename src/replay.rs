@@ -0,0 +1,352 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::MainGameState;
+use crate::controls::PlayerInput;
+use crate::level::{Level, LevelTiles};
+use crate::menu::MenuState;
+use crate::player::Player;
+use crate::rng::GameRng;
+use crate::save::PendingLoad;
+use crate::settings::Settings;
+use crate::utils::{config_dir, read_versioned_json, write_json_compact, VersionedJsonError};
+use crate::waves::{WaveConfig, WaveDirector};
+
+// Bump this whenever the format changes, older replays are turned away rather than played wrong
+pub const REPLAY_VERSION: u32 = 3;
+
+// Everything the player did in one tick of the game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayTick {
+    pub input: PlayerInput,
+    // Where the player was aiming in the world, whether it was the mouse or a stick
+    pub aim: Vec2,
+    // Can be changed from the pause menu part way through a run
+    pub auto_reload: bool,
+}
+
+// A whole run from its first tick, along with everything else that decided how it went
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub levels: Vec<Level>,
+    // As waves.json was when the run was played, so changing it later doesn't change the replay
+    pub waves: WaveConfig,
+    pub ticks: Vec<ReplayTick>,
+}
+
+impl Replay {
+    // Where the last run played is written, ready to be watched or attached to a bug report
+    pub fn last_run_path() -> PathBuf {
+        config_dir().join("last_replay.json")
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, VersionedJsonError> {
+        read_versioned_json(path, REPLAY_VERSION)
+    }
+
+    // Not pretty printed, there's one entry for every tick of the run
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_json_compact(path, self)
+    }
+}
+
+// The run being played now, written out when it ends. The levels and waves are added then.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    seed: u64,
+    ticks: Vec<ReplayTick>,
+}
+
+// Put in place by --replay. Its ticks stand in for the player's input until they run out.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    // The next tick to play
+    tick: usize,
+    // Auto reload as it was set for the tick being played
+    pub auto_reload: bool,
+    // Set once the replay has started, so the main menu knows it's over when it comes back
+    started: bool,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayback {
+            replay: replay,
+            tick: 0,
+            auto_reload: false,
+            started: false,
+        }
+    }
+}
+
+// A continued run can't be played again from its seed, it started part way through
+pub fn start_recording(
+    mut commands: Commands,
+    game_rng: Res<GameRng>,
+    pending_load: Option<Res<PendingLoad>>,
+){
+    if pending_load.is_some() {
+        commands.remove_resource::<ReplayRecorder>();
+        info!("Continued runs aren't recorded");
+        return;
+    }
+    commands.insert_resource(ReplayRecorder {
+        seed: game_rng.seed(),
+        ticks: Vec::new(),
+    });
+}
+
+// First thing in every tick, before anything has moved or fired
+pub fn record_tick(
+    input: Res<PlayerInput>,
+    settings: Res<Settings>,
+    players: Query<&Player>,
+    mut recorder: ResMut<ReplayRecorder>,
+){
+    let mut input = input.clone();
+    // Pausing doesn't change the run, paused frames aren't ticks at all
+    input.pause = false;
+    recorder.ticks.push(ReplayTick {
+        input: input,
+        aim: players.get_single().map_or(Vec2::ZERO, |player| player.mouse),
+        auto_reload: settings.auto_reload,
+    });
+}
+
+pub fn save_recording(
+    mut commands: Commands,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    tiles: Option<Res<LevelTiles>>,
+    director: Option<Res<WaveDirector>>,
+){
+    let (Some(mut recorder), Some(tiles), Some(director)) = (recorder, tiles, director) else {
+        return;
+    };
+    commands.remove_resource::<ReplayRecorder>();
+
+    let replay = Replay {
+        version: REPLAY_VERSION,
+        seed: recorder.seed,
+        levels: tiles.levels.values().cloned().collect(),
+        waves: director.config.clone(),
+        ticks: std::mem::take(&mut recorder.ticks),
+    };
+    let path = Replay::last_run_path();
+    match replay.save(&path) {
+        Ok(()) => info!("Replay of the run written to {}", path.display()),
+        Err(err) => warn!("Couldn't write the replay: {}", err),
+    }
+}
+
+// Starts the replay from the main menu as if Play was pressed, and drops it once the
+// viewer is back there so the next run is their own
+pub fn replay_from_menu(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+){
+    if playback.started {
+        commands.remove_resource::<ReplayPlayback>();
+        return;
+    }
+    game_state.set(MainGameState::Game);
+    menu_state.set(MenuState::Disabled);
+}
+
+// Restarting or retrying watches the replay again from the start
+pub fn start_playback(mut playback: ResMut<ReplayPlayback>) {
+    playback.tick = 0;
+    playback.started = true;
+}
+
+// Stands in for the player at the start of every tick
+pub fn play_tick(
+    mut playback: ResMut<ReplayPlayback>,
+    mut input: ResMut<PlayerInput>,
+    mut players: Query<&mut Player>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+){
+    // Pausing is left to whoever is watching
+    let pause = input.pause;
+    let Some(tick) = playback.replay.ticks.get(playback.tick).cloned() else {
+        // The run was quit rather than lost, so there's no game over to go to
+        *input = PlayerInput::default();
+        input.pause = pause;
+        game_state.set(MainGameState::Menu);
+        return;
+    };
+    playback.tick += 1;
+    playback.auto_reload = tick.auto_reload;
+    *input = tick.input;
+    input.pause = pause;
+
+    // Takes the place of track_mouse and track_stick
+    if let Ok(mut player) = players.get_single_mut() {
+        player.mouse = tick.aim;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{self, RunStats, TICK_RATE};
+    use crate::hud::{AmmoChanged, HealthChanged, ScoreChanged, ZombieKilled};
+    use crate::inventory::GrantItem;
+    use crate::navigation::NavGrid;
+    use crate::player;
+    use crate::score::Scoring;
+    use crate::sound::PlaySound;
+    use crate::utils::parse_versioned_json;
+    use crate::waves::{WaveCleared, WaveStarted};
+    use crate::zombie::Zombie;
+    use crate::GameDetails;
+
+    // Ten seconds, long enough for the first wave to arrive
+    const TICKS: usize = 600;
+
+    fn levels() -> Vec<Level> {
+        LevelTiles::load().unwrap().levels.into_values().collect()
+    }
+
+    fn spawn_player(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    ){
+        player::create_player(&mut commands, &asset_server, &mut texture_atlases);
+    }
+
+    // Just the game's ticks, set up the way game_setup does it, with no window, sound or HUD
+    fn headless_app(seed: u64, levels: Vec<Level>, waves: WaveConfig) -> App {
+        let tiles = LevelTiles::from_levels(levels);
+        let mut game_rng = GameRng::new(None);
+        game_rng.reseed(seed);
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .add_asset::<TextureAtlas>()
+            .add_state::<MainGameState>()
+            .add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_event::<GrantItem>()
+            .add_event::<HealthChanged>()
+            .add_event::<AmmoChanged>()
+            .add_event::<ScoreChanged>()
+            .add_event::<ZombieKilled>()
+            .add_event::<PlaySound>()
+            .init_resource::<PlayerInput>()
+            .insert_resource(Settings::default())
+            .insert_resource(game_rng)
+            .insert_resource(FixedTime::new_from_secs(1.0 / TICK_RATE))
+            .insert_resource(GameDetails { bounds: tiles.bounds() })
+            .insert_resource(NavGrid::new(&tiles))
+            .insert_resource(WaveDirector::new(waves, &tiles))
+            .insert_resource(tiles)
+            .insert_resource(RunStats::default())
+            .insert_resource(Scoring::default())
+            .add_systems(Startup, spawn_player)
+            .add_systems(FixedUpdate, game::simulation().run_if(player::player_alive));
+        app.world.run_schedule(Startup);
+        app
+    }
+
+    // Everything about the run that a replay should get exactly the same
+    fn outcome(app: &mut App) -> (f32, u32, u32, u32, u32, Vec2, i32) {
+        let stats = app.world.resource::<RunStats>();
+        let stats = (stats.time_survived, stats.zombies_killed, stats.score, stats.wave, stats.shots_hit);
+        let player = app.world.query::<&Player>().single(&app.world);
+        (stats.0, stats.1, stats.2, stats.3, stats.4, player.loc, player.health)
+    }
+
+    // Walks about and shoots at the nearest zombie, the way a player at the keyboard would
+    fn play(app: &mut App, tick: usize) {
+        let zombies: Vec<Vec2> = app.world.query::<&Zombie>().iter(&app.world).map(|zombie| zombie.pos).collect();
+        let mut player = app.world.query::<&mut Player>().single_mut(&mut app.world);
+        let target = zombies.into_iter()
+            .min_by(|a, b| a.distance(player.loc).total_cmp(&b.distance(player.loc)))
+            .unwrap_or(player.loc + Vec2::X);
+        player.mouse = target;
+
+        let mut input = app.world.resource_mut::<PlayerInput>();
+        input.movement = if (tick / 120) % 2 == 0 { Vec2::X } else { Vec2::Y };
+        input.fire = true;
+        input.fire_pressed = tick % 8 == 0;
+        input.reload = tick == 300;
+        input.select_weapon = if tick == 200 { Some(1) } else { None };
+    }
+
+    #[test]
+    fn replay_reproduces_its_run() {
+        let seed = 1234;
+        let mut recording = headless_app(seed, levels(), WaveConfig::load().unwrap());
+        recording.insert_resource(ReplayRecorder {
+            seed: seed,
+            ticks: Vec::new(),
+        });
+        for tick in 0..TICKS {
+            play(&mut recording, tick);
+            recording.world.run_schedule(FixedUpdate);
+        }
+        let recorded = outcome(&mut recording);
+        assert!(recorded.3 >= 1, "the first wave never started");
+
+        // Through the file format, the way a replay is really watched
+        let replay = Replay {
+            version: REPLAY_VERSION,
+            seed: seed,
+            levels: levels(),
+            waves: WaveConfig::load().unwrap(),
+            ticks: recording.world.remove_resource::<ReplayRecorder>().unwrap().ticks,
+        };
+        assert_eq!(replay.ticks.len(), TICKS);
+        let json = serde_json::to_string(&replay).unwrap();
+        let replay = parse_versioned_json::<Replay>(Path::new("replay.json"), &json, REPLAY_VERSION).unwrap();
+
+        // A different seed to start with, the replay's own has to take over
+        let mut playback = headless_app(seed + 1, replay.levels.clone(), replay.waves.clone());
+        playback.world.resource_mut::<GameRng>().reseed(replay.seed);
+        playback.insert_resource(ReplayPlayback::new(replay));
+        for _ in 0..TICKS {
+            playback.world.run_schedule(FixedUpdate);
+        }
+
+        assert_eq!(outcome(&mut playback), recorded);
+    }
+
+    fn replay() -> Replay {
+        let mut input = PlayerInput::default();
+        input.movement = Vec2::new(0.5, -1.0);
+        input.fire = true;
+        input.select_weapon = Some(2);
+        input.cycle_weapon = -1;
+        Replay {
+            version: REPLAY_VERSION,
+            seed: u64::MAX,
+            levels: vec![Level::new(0, 0, "Start tile")],
+            waves: WaveConfig::load().unwrap(),
+            ticks: vec![
+                ReplayTick { input: PlayerInput::default(), aim: Vec2::ZERO, auto_reload: true },
+                ReplayTick { input: input, aim: Vec2::new(640.25, -12.5), auto_reload: false },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = serde_json::to_string(&replay()).unwrap();
+        let loaded = parse_versioned_json::<Replay>(Path::new("replay.json"), &json, REPLAY_VERSION).unwrap();
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        assert_eq!(loaded.seed, u64::MAX);
+        assert_eq!(loaded.waves.waves.len(), replay().waves.waves.len());
+        assert_eq!(loaded.waves.enemy_types["runner"].speed, replay().waves.enemy_types["runner"].speed);
+        assert_eq!(loaded.ticks[1].aim, Vec2::new(640.25, -12.5));
+        assert_eq!(loaded.ticks[1].input.select_weapon, Some(2));
+        assert!(!loaded.ticks[1].auto_reload);
+    }
+}
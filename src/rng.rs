@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+// Every random decision in the game comes from here, so a run can be played again exactly from
+// its seed. ChaCha gives the same numbers on every platform and version, unlike StdRng.
+//
+// Gameplay and cosmetics draw from separate streams of the same seed, so turning blood off or
+// a zombie groaning at a different moment can never change where the bullets go.
+#[derive(Resource)]
+pub struct GameRng {
+    // Seed given on the command line, used for every run instead of a fresh one
+    fixed_seed: Option<u64>,
+    seed: u64,
+    gameplay: ChaCha8Rng,
+    cosmetic: ChaCha8Rng,
+}
+
+const GAMEPLAY_STREAM: u64 = 0;
+const COSMETIC_STREAM: u64 = 1;
+
+// How far a run has drawn from each stream, so a saved run carries on with the same numbers
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct RngState {
+    pub seed: u64,
+    pub gameplay: u128,
+    pub cosmetic: u128,
+}
+
+impl GameRng {
+    pub fn new(fixed_seed: Option<u64>) -> Self {
+        let seed = fixed_seed.unwrap_or(0);
+        GameRng {
+            fixed_seed: fixed_seed,
+            seed: seed,
+            gameplay: GameRng::stream(seed, GAMEPLAY_STREAM),
+            cosmetic: GameRng::stream(seed, COSMETIC_STREAM),
+        }
+    }
+
+    fn stream(seed: u64, stream: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(stream);
+        rng
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Go back to the start of both streams for this seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.gameplay = GameRng::stream(seed, GAMEPLAY_STREAM);
+        self.cosmetic = GameRng::stream(seed, COSMETIC_STREAM);
+    }
+
+    pub fn state(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            gameplay: self.gameplay.get_word_pos(),
+            cosmetic: self.cosmetic.get_word_pos(),
+        }
+    }
+
+    // Picks up a saved run's streams exactly where they were left
+    pub fn restore(&mut self, state: RngState) {
+        self.reseed(state.seed);
+        self.gameplay.set_word_pos(state.gameplay);
+        self.cosmetic.set_word_pos(state.cosmetic);
+        info!("Continuing run seed {}", state.seed);
+    }
+
+    // Called as each run starts. Without a seed on the command line every run gets a new one,
+    // logged so a run that went wrong can be played again with --seed.
+    pub fn start_run(&mut self) {
+        let seed = self.fixed_seed.unwrap_or_else(rand::random);
+        self.reseed(seed);
+        info!("Run seed {}", seed);
+    }
+
+    // Anything that changes how the run plays out, e.g. bullet spread
+    pub fn gameplay(&mut self) -> &mut ChaCha8Rng {
+        &mut self.gameplay
+    }
+
+    // Anything that's only for show, e.g. blood and sounds
+    pub fn cosmetic(&mut self) -> &mut ChaCha8Rng {
+        &mut self.cosmetic
+    }
+}
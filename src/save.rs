@@ -0,0 +1,539 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use super::MainGameState;
+use crate::bullet::{self, Bullet};
+use crate::game::RunStats;
+use crate::hud::{AmmoChanged, HealthChanged, ScoreChanged};
+use crate::inventory::{Consumable, Inventory};
+use crate::menu::MenuState;
+use crate::player::Player;
+use crate::interpolation::Interpolated;
+use crate::replay::ReplayPlayback;
+use crate::rng::{GameRng, RngState};
+use crate::score::Scoring;
+use crate::utils::{config_dir, read_versioned_json, write_json, VersionedJsonError};
+use crate::waves::{WaveDirector, WavePhase, WaveStarted};
+use crate::weapon::{self, Caliber, Loadout, WeaponSlot};
+use crate::zombie::{self, Zombie, ZombieState};
+
+// Bump this whenever the format changes, and teach SaveGame::load about the old one if it can still be read
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub loc: Vec2,
+    pub health: i32,
+    pub invulnerable: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedZombie {
+    pub pos: Vec2,
+    pub loc: Vec<Vec2>,
+    pub cur_loc: usize,
+    pub health: i32,
+    pub speed: f32,
+    pub route: Vec<Vec2>,
+    pub route_index: usize,
+    pub path_goal: Vec2,
+    // Where it is in hunting the player, so a chase carries on where it left off
+    pub state: ZombieState,
+    pub facing: f32,
+    pub last_seen: Vec2,
+    pub search_time: f32,
+    pub attack_cooldown: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedBullet {
+    pub loc: Vec2,
+    pub angle: f32,
+    pub damage: i32,
+    pub speed: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedWaves {
+    pub wave: u32,
+    pub phase: WavePhase,
+    // How far through the break or spawn delay it was
+    pub timer_duration: f32,
+    pub timer_elapsed: f32,
+    pub timer_repeating: bool,
+    pub to_spawn: Vec<String>,
+    pub next_point: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedWeapon {
+    // Weapons are looked up by name, so a saved game picks up any rebalancing
+    pub name: String,
+    pub in_magazine: u32,
+    // Seconds left of a reload part way through
+    pub reloading: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedInventory {
+    pub ammo: Vec<(Caliber, u32)>,
+    pub weapons: Vec<SavedWeapon>,
+    pub current: usize,
+    // Seconds until the gun that's out can fire again
+    pub cooldown: f32,
+    pub consumables: Vec<(Consumable, u32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedScore {
+    pub time_survived: f32,
+    pub zombies_killed: u32,
+    pub score: u32,
+    pub wave: u32,
+    pub shots_hit: u32,
+    pub multiplier: u32,
+    pub combo_time: f32,
+    // How the shooting has gone this wave, for the accuracy bonus
+    pub wave_shots: u32,
+    pub wave_start_hits: u32,
+}
+
+// A run part way through, written when the player saves and quits
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub player: SavedPlayer,
+    pub zombies: Vec<SavedZombie>,
+    pub bullets: Vec<SavedBullet>,
+    pub waves: SavedWaves,
+    pub inventory: SavedInventory,
+    pub score: SavedScore,
+    pub rng: RngState,
+}
+
+impl SaveGame {
+    pub fn path() -> PathBuf {
+        config_dir().join("save.json")
+    }
+
+    pub fn exists() -> bool {
+        SaveGame::path().exists()
+    }
+
+    pub fn load() -> Result<SaveGame, VersionedJsonError> {
+        read_versioned_json(&SaveGame::path(), SAVE_VERSION)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_json(&SaveGame::path(), self)
+    }
+
+    // A run can only be continued once
+    pub fn delete() {
+        let path = SaveGame::path();
+        if let Err(err) = std::fs::remove_file(&path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!("Couldn't remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+// Put in place by the Continue button, and picked up when the game starts
+#[derive(Resource)]
+pub struct PendingLoad(pub SaveGame);
+
+// Send to save the run and head back to the main menu
+#[derive(Event)]
+pub struct SaveRun;
+
+// Where to tell the player the save didn't work
+#[derive(Component)]
+pub struct SaveMessage;
+
+#[allow(clippy::too_many_arguments)]
+pub fn save_run(
+    mut requests: EventReader<SaveRun>,
+    players: Query<(&Player, &Inventory)>,
+    zombies: Query<&Zombie>,
+    bullets: Query<&Bullet>,
+    director: Res<WaveDirector>,
+    stats: Res<RunStats>,
+    scoring: Res<Scoring>,
+    mut messages: Query<&mut Text, With<SaveMessage>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut game_state: ResMut<NextState<MainGameState>>,
+    playback: Option<Res<ReplayPlayback>>,
+    game_rng: Res<GameRng>,
+){
+    if requests.is_empty() {
+        return;
+    }
+    requests.clear();
+    // It would only replace the player's own save with someone else's run
+    if playback.is_some() {
+        for mut text in messages.iter_mut() {
+            text.sections[0].value = "A replay can't be saved".to_string();
+        }
+        return;
+    }
+    if players.is_empty() {
+        return;
+    }
+    let (player, inventory) = players.single();
+
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        player: SavedPlayer {
+            loc: player.loc,
+            health: player.health,
+            invulnerable: player.invulnerable,
+        },
+        zombies: zombies.iter().map(|zombie| SavedZombie {
+            pos: zombie.pos,
+            loc: zombie.loc.clone(),
+            cur_loc: zombie.cur_loc,
+            health: zombie.health,
+            speed: zombie.speed,
+            route: zombie.route.clone(),
+            route_index: zombie.route_index,
+            path_goal: zombie.path_goal,
+            state: zombie.state,
+            facing: zombie.facing,
+            last_seen: zombie.last_seen,
+            search_time: zombie.search_time,
+            attack_cooldown: zombie.attack_cooldown,
+        }).collect(),
+        bullets: bullets.iter().map(|bullet| SavedBullet {
+            loc: bullet.loc,
+            angle: bullet.angle,
+            damage: bullet.damage,
+            speed: bullet.speed,
+        }).collect(),
+        waves: SavedWaves {
+            wave: director.wave,
+            phase: director.phase,
+            timer_duration: director.timer.duration().as_secs_f32(),
+            timer_elapsed: director.timer.elapsed_secs(),
+            timer_repeating: director.timer.mode() == TimerMode::Repeating,
+            to_spawn: director.to_spawn.clone(),
+            next_point: director.next_point,
+        },
+        inventory: SavedInventory {
+            ammo: inventory.ammo.iter().map(|(caliber, amount)| (*caliber, *amount)).collect(),
+            weapons: inventory.loadout.slots.iter().map(|slot| SavedWeapon {
+                name: slot.weapon.name.to_string(),
+                in_magazine: slot.in_magazine,
+                reloading: slot.reloading,
+            }).collect(),
+            current: inventory.loadout.current,
+            cooldown: inventory.loadout.cooldown,
+            consumables: inventory.consumables.iter().map(|(consumable, count)| (*consumable, *count)).collect(),
+        },
+        score: SavedScore {
+            time_survived: stats.time_survived,
+            zombies_killed: stats.zombies_killed,
+            score: stats.score,
+            wave: stats.wave,
+            shots_hit: stats.shots_hit,
+            multiplier: scoring.multiplier,
+            combo_time: scoring.combo_time,
+            wave_shots: scoring.wave_shots,
+            wave_start_hits: scoring.wave_start_hits,
+        },
+        rng: game_rng.state(),
+    };
+
+    match save.save() {
+        Ok(()) => {
+            game_state.set(MainGameState::Menu);
+            menu_state.set(MenuState::Main);
+        }
+        // Stay in the game rather than lose the run
+        Err(err) => {
+            for mut text in messages.iter_mut() {
+                text.sections[0].value = format!("Couldn't save: {}", err);
+            }
+        }
+    }
+}
+
+fn restore_inventory(saved: &SavedInventory) -> Inventory {
+    let mut slots = Vec::new();
+    for saved_weapon in &saved.weapons {
+        match weapon::WEAPONS.iter().copied().find(|weapon| weapon.name == saved_weapon.name) {
+            Some(weapon) => {
+                let mut slot = WeaponSlot::new(weapon);
+                slot.in_magazine = saved_weapon.in_magazine.min(weapon.magazine);
+                slot.reloading = saved_weapon.reloading.clamp(0.0, weapon.reload_time);
+                slots.push(slot);
+            }
+            None => warn!("The saved game has a {} that no longer exists, leaving it out", saved_weapon.name),
+        }
+    }
+
+    let mut loadout = Loadout::new(&[]);
+    loadout.current = saved.current.min(slots.len().saturating_sub(1));
+    loadout.cooldown = saved.cooldown.max(0.0);
+    loadout.slots = slots;
+    // Nobody should be left empty handed
+    if loadout.slots.is_empty() {
+        loadout = Loadout::new(&weapon::WEAPONS);
+    }
+
+    Inventory {
+        ammo: saved.ammo.iter().copied().collect(),
+        loadout: loadout,
+        consumables: saved.consumables.iter().copied().collect(),
+    }
+}
+
+fn restore_waves(saved: &SavedWaves, director: &mut WaveDirector) {
+    director.wave = saved.wave;
+    director.phase = saved.phase;
+    let mut to_spawn = saved.to_spawn.clone();
+    to_spawn.retain(|name| {
+        let known = director.config.enemy_types.contains_key(name);
+        if !known {
+            warn!("The saved game has a {} still to spawn that no longer exists, leaving it out", name);
+        }
+        known
+    });
+    director.to_spawn = to_spawn;
+    director.next_point = saved.next_point;
+    let mode = if saved.timer_repeating { TimerMode::Repeating } else { TimerMode::Once };
+    director.timer = Timer::from_seconds(saved.timer_duration, mode);
+    director.timer.set_elapsed(std::time::Duration::from_secs_f32(saved.timer_elapsed));
+}
+
+// Called from game setup once the level and the wave director are ready. Puts the run back
+// the way it was saved, and fills in the HUD to match.
+#[allow(clippy::too_many_arguments)]
+pub fn restore_run(
+    save: &SaveGame,
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    player_entity: Entity,
+    game_rng: &mut GameRng,
+    director: &mut WaveDirector,
+    health: &mut EventWriter<HealthChanged>,
+    ammo: &mut EventWriter<AmmoChanged>,
+    score: &mut EventWriter<ScoreChanged>,
+    started: &mut EventWriter<WaveStarted>,
+){
+    game_rng.restore(save.rng);
+
+    // The movers put the transforms where the saved positions say on the next tick
+    let mut player = Player::default();
+    player.loc = save.player.loc;
+    player.mouse = save.player.loc;
+    player.health = save.player.health;
+    player.invulnerable = save.player.invulnerable;
+    let inventory = restore_inventory(&save.inventory);
+    ammo.send(AmmoChanged::new(&inventory));
+    health.send(HealthChanged { health: player.health });
+    commands.entity(player_entity).insert(Interpolated::new(player.loc)).insert(player).insert(inventory);
+
+    let texture_atlas = zombie::zombie_texture_atlas(asset_server, texture_atlases);
+    for saved in &save.zombies {
+        if saved.route.is_empty() {
+            continue;
+        }
+        let mut zombie = Zombie::new(saved.route.clone(), saved.route_index % saved.route.len());
+        zombie.pos = saved.pos;
+        zombie.loc = saved.loc.clone();
+        zombie.cur_loc = saved.cur_loc;
+        zombie.health = saved.health;
+        zombie.speed = saved.speed;
+        zombie.path_goal = saved.path_goal;
+        zombie.state = saved.state;
+        zombie.facing = saved.facing;
+        zombie.last_seen = saved.last_seen;
+        zombie.search_time = saved.search_time;
+        zombie.attack_cooldown = saved.attack_cooldown;
+        zombie::spawn_zombie(commands, texture_atlas.clone(), zombie);
+    }
+
+    let texture_handle = bullet::bullet_texture(asset_server);
+    for saved in &save.bullets {
+        bullet::spawn_bullet(commands, texture_handle.clone(), Bullet {
+            loc: saved.loc,
+            angle: saved.angle,
+            hit_box: bullet::BULLET_HIT_BOX,
+            damage: saved.damage,
+            speed: saved.speed,
+        });
+    }
+
+    let waves = &save.waves;
+    restore_waves(waves, director);
+
+    let remaining = (save.zombies.len() + director.to_spawn.len()) as u32;
+    // Puts the wave on the HUD, the wave itself carries on
+    if waves.wave > 0 {
+        started.send(WaveStarted {
+            wave: waves.wave,
+            zombies: remaining,
+            continued: true,
+        });
+    }
+
+    let saved_score = &save.score;
+    commands.insert_resource(RunStats {
+        time_survived: saved_score.time_survived,
+        zombies_killed: saved_score.zombies_killed,
+        score: saved_score.score,
+        wave: saved_score.wave,
+        shots_hit: saved_score.shots_hit,
+    });
+    commands.insert_resource(Scoring {
+        multiplier: saved_score.multiplier.max(1),
+        combo_time: saved_score.combo_time,
+        // The bullets still in the air were counted when they were fired, and are counted
+        // again as new bullets when they are put back
+        wave_shots: saved_score.wave_shots.saturating_sub(save.bullets.len() as u32),
+        wave_start_hits: saved_score.wave_start_hits,
+    });
+    score.send(ScoreChanged {
+        score: saved_score.score,
+        multiplier: saved_score.multiplier.max(1),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::LevelTiles;
+    use crate::utils::parse_versioned_json;
+    use std::path::Path;
+    use crate::waves::WaveConfig;
+
+    fn save_game() -> SaveGame {
+        SaveGame {
+            version: SAVE_VERSION,
+            player: SavedPlayer {
+                loc: Vec2::new(320.0, 180.5),
+                health: 7,
+                invulnerable: 0.4,
+            },
+            zombies: vec![SavedZombie {
+                pos: Vec2::new(900.0, 400.0),
+                loc: vec![Vec2::new(860.0, 420.0), Vec2::new(320.0, 180.0)],
+                cur_loc: 1,
+                health: 3,
+                speed: 154.5,
+                route: vec![Vec2::new(150.0, 300.0), Vec2::new(900.0, 600.0)],
+                route_index: 1,
+                path_goal: Vec2::new(320.0, 180.0),
+                state: ZombieState::Chase,
+                facing: 2.5,
+                last_seen: Vec2::new(320.0, 180.0),
+                search_time: 0.0,
+                attack_cooldown: 0.7,
+            }],
+            bullets: vec![SavedBullet {
+                loc: Vec2::new(400.0, 220.0),
+                angle: 0.75,
+                damage: 2,
+                speed: 600.0,
+            }],
+            waves: SavedWaves {
+                wave: 3,
+                phase: WavePhase::Spawning,
+                timer_duration: 1.0,
+                timer_elapsed: 0.25,
+                timer_repeating: true,
+                to_spawn: vec!["zombie".to_string(), "runner".to_string()],
+                next_point: 1,
+            },
+            inventory: SavedInventory {
+                ammo: vec![(Caliber::NineMil, 84), (Caliber::TwelveGauge, 6)],
+                weapons: vec![
+                    SavedWeapon { name: "Pistol".to_string(), in_magazine: 4, reloading: 0.5 },
+                    SavedWeapon { name: "Shotgun".to_string(), in_magazine: 6, reloading: 0.0 },
+                ],
+                current: 0,
+                cooldown: 0.1,
+                consumables: vec![(Consumable::Medkit, 2)],
+            },
+            score: SavedScore {
+                time_survived: 95.5,
+                zombies_killed: 14,
+                score: 2900,
+                wave: 3,
+                shots_hit: 30,
+                multiplier: 2,
+                combo_time: 1.25,
+                wave_shots: 12,
+                wave_start_hits: 21,
+            },
+            rng: RngState {
+                seed: 0xDEAD_BEEF_CAFE,
+                gameplay: 1234,
+                cosmetic: 56,
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = serde_json::to_string_pretty(&save_game()).unwrap();
+        let loaded: SaveGame = parse_versioned_json(Path::new("save.json"), &json, SAVE_VERSION).unwrap();
+        assert_eq!(serde_json::to_string_pretty(&loaded).unwrap(), json);
+
+        let zombie = &loaded.zombies[0];
+        assert_eq!(zombie.state, ZombieState::Chase);
+        assert_eq!(zombie.attack_cooldown, 0.7);
+        assert_eq!(loaded.inventory.weapons[0].reloading, 0.5);
+        assert_eq!(loaded.player.invulnerable, 0.4);
+        assert_eq!(loaded.rng, save_game().rng);
+    }
+
+    #[test]
+    fn restores_the_inventory() {
+        let inventory = restore_inventory(&save_game().inventory);
+        assert_eq!(inventory.ammo(Caliber::NineMil), 84);
+        assert_eq!(inventory.consumables(Consumable::Medkit), 2);
+        assert_eq!(inventory.loadout.slots.len(), 2);
+        assert_eq!(inventory.loadout.current().in_magazine, 4);
+        assert!(inventory.loadout.current().is_reloading());
+        assert_eq!(inventory.loadout.cooldown, 0.1);
+    }
+
+    #[test]
+    fn unknown_weapons_are_left_out() {
+        let mut saved = save_game().inventory;
+        saved.weapons[0].name = "Railgun".to_string();
+        let inventory = restore_inventory(&saved);
+        assert_eq!(inventory.loadout.slots.len(), 1);
+        assert_eq!(inventory.loadout.current().weapon.name, "Shotgun");
+    }
+
+    fn director() -> WaveDirector {
+        WaveDirector::new(WaveConfig::load().unwrap(), &LevelTiles::from_levels(Vec::new()))
+    }
+
+    #[test]
+    fn restores_the_waves() {
+        let mut director = director();
+        restore_waves(&save_game().waves, &mut director);
+        assert_eq!(director.wave, 3);
+        assert_eq!(director.phase, WavePhase::Spawning);
+        assert_eq!(director.to_spawn, vec!["zombie", "runner"]);
+        assert_eq!(director.timer.elapsed_secs(), 0.25);
+        assert_eq!(director.next_point, 1);
+    }
+
+    #[test]
+    fn unknown_zombie_types_are_left_out() {
+        // Say runners had been taken out of waves.json since the run was saved
+        let mut saved = save_game().waves;
+        saved.to_spawn = vec!["runner".to_string(), "zombie".to_string(), "crawler".to_string()];
+        let mut director = director();
+        director.config.enemy_types.remove("runner");
+        restore_waves(&saved, &mut director);
+        assert_eq!(director.to_spawn, vec!["zombie"]);
+    }
+}
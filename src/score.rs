@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::bullet::Bullet;
+use crate::game::RunStats;
+use crate::hud::{ScoreChanged, ZombieKilled};
+use crate::utils::{config_dir, read_json, write_json};
+use crate::waves::{WaveCleared, WaveStarted};
+
+// Points for each zombie killed, before the combo multiplier
+const KILL_SCORE: u32 = 100;
+// Points for clearing a wave, times the wave number
+const WAVE_CLEAR_SCORE: u32 = 500;
+// Bonus for clearing a wave without missing, scaled down by the shots that missed
+const ACCURACY_BONUS: u32 = 1000;
+// Seconds after a kill for the next one to keep the combo going
+const COMBO_WINDOW: f32 = 2.0;
+const MAX_COMBO: u32 = 5;
+
+// How many runs the high score table keeps
+pub const HIGH_SCORE_COUNT: usize = 10;
+// Longest name that can be entered for a high score
+pub const MAX_NAME_LENGTH: usize = 12;
+
+// Keeps track of the combo and the shooting in the current wave
+#[derive(Resource)]
+pub struct Scoring {
+    // 1 while there's no combo going
+    pub multiplier: u32,
+    pub combo_time: f32,
+    pub wave_shots: u32,
+    // RunStats::shots_hit when the wave started
+    pub wave_start_hits: u32,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            multiplier: 1,
+            combo_time: 0.0,
+            wave_shots: 0,
+            wave_start_hits: 0,
+        }
+    }
+}
+
+impl Scoring {
+    // Runs the combo down, returning true when it has just run out
+    pub fn tick(&mut self, seconds: f32) -> bool {
+        if self.combo_time <= 0.0 {
+            return false;
+        }
+        self.combo_time -= seconds;
+        if self.combo_time > 0.0 {
+            return false;
+        }
+        self.multiplier = 1;
+        true
+    }
+
+    // Points for a kill. Each kill inside the window makes the next one worth more.
+    pub fn kill(&mut self) -> u32 {
+        let points = KILL_SCORE * self.multiplier;
+        if self.combo_time > 0.0 {
+            self.multiplier = (self.multiplier + 1).min(MAX_COMBO);
+        }
+        self.combo_time = COMBO_WINDOW;
+        points
+    }
+
+    pub fn start_wave(&mut self, shots_hit: u32) {
+        self.wave_shots = 0;
+        self.wave_start_hits = shots_hit;
+    }
+
+    // Points for clearing a wave, given the run's hits so far
+    pub fn wave_cleared(&self, wave: u32, shots_hit: u32) -> u32 {
+        let hits = shots_hit - self.wave_start_hits;
+        let accuracy = if self.wave_shots == 0 {
+            0.0
+        } else {
+            (hits as f32 / self.wave_shots as f32).min(1.0)
+        };
+        WAVE_CLEAR_SCORE * wave + (ACCURACY_BONUS as f32 * accuracy) as u32
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn score_events(
+    time: Res<FixedTime>,
+    mut killed: EventReader<ZombieKilled>,
+    mut started: EventReader<WaveStarted>,
+    mut cleared: EventReader<WaveCleared>,
+    new_bullets: Query<(), Added<Bullet>>,
+    mut stats: ResMut<RunStats>,
+    mut scoring: ResMut<Scoring>,
+    mut score: EventWriter<ScoreChanged>,
+){
+    let mut changed = false;
+
+    scoring.wave_shots += new_bullets.iter().count() as u32;
+
+    if scoring.tick(time.period.as_secs_f32()) {
+        changed = true;
+    }
+
+    for _ in killed.iter() {
+        stats.score += scoring.kill();
+        changed = true;
+    }
+
+    for wave in started.iter() {
+        stats.wave = wave.wave;
+        if !wave.continued {
+            scoring.start_wave(stats.shots_hit);
+        }
+    }
+
+    for wave in cleared.iter() {
+        stats.score += scoring.wave_cleared(wave.wave, stats.shots_hit);
+        changed = true;
+    }
+
+    if changed {
+        score.send(ScoreChanged {
+            score: stats.score,
+            multiplier: scoring.multiplier,
+        });
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub wave: u32,
+    pub kills: u32,
+}
+
+// The best runs, highest first
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn path() -> PathBuf {
+        config_dir().join("high_scores.json")
+    }
+
+    // A missing or broken table starts again empty rather than stopping the game
+    pub fn load() -> HighScores {
+        match read_json::<HighScores>(&HighScores::path()) {
+            Ok(Some(mut scores)) => {
+                scores.entries.sort_by(|a, b| b.score.cmp(&a.score));
+                scores.entries.truncate(HIGH_SCORE_COUNT);
+                scores
+            }
+            Ok(None) => HighScores::default(),
+            Err(err) => {
+                warn!("Couldn't read {}, starting a new high score table", err);
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_json(&HighScores::path(), self)
+    }
+
+    // Whether a run with this score would make it onto the table
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < HIGH_SCORE_COUNT
+            || self.entries.last().is_some_and(|lowest| score > lowest.score))
+    }
+
+    // Returns the place it went in, counting from 1, if it made the table.
+    // Ties go below the scores already there.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let index = self.entries.iter().position(|other| entry.score > other.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        self.entries.truncate(HIGH_SCORE_COUNT);
+        Some(index + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            score: score,
+            wave: 1,
+            kills: 0,
+        }
+    }
+
+    // A full table scoring 1000, 900, ... 100
+    fn full_table() -> HighScores {
+        HighScores {
+            entries: (0..HIGH_SCORE_COUNT as u32).map(|place| entry("AAA", 1000 - place * 100)).collect(),
+        }
+    }
+
+    fn names(scores: &HighScores) -> Vec<&str> {
+        scores.entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn anything_scoring_makes_an_empty_table() {
+        let mut scores = HighScores::default();
+        assert!(!scores.qualifies(0));
+        assert_eq!(scores.insert(entry("Nil", 0)), None);
+        assert!(scores.qualifies(1));
+        assert_eq!(scores.insert(entry("One", 1)), Some(1));
+    }
+
+    #[test]
+    fn full_table_needs_a_better_score() {
+        let mut scores = full_table();
+        // Tying the lowest isn't enough
+        assert!(!scores.qualifies(100));
+        assert_eq!(scores.insert(entry("Tie", 100)), None);
+        assert!(scores.qualifies(101));
+        assert_eq!(scores.entries.len(), HIGH_SCORE_COUNT);
+    }
+
+    #[test]
+    fn ties_go_below() {
+        let mut scores = HighScores::default();
+        scores.insert(entry("First", 500));
+        scores.insert(entry("Low", 100));
+        assert_eq!(scores.insert(entry("Second", 500)), Some(2));
+        assert_eq!(names(&scores), vec!["First", "Second", "Low"]);
+    }
+
+    #[test]
+    fn table_is_cut_to_size() {
+        let mut scores = full_table();
+        assert_eq!(scores.insert(entry("Top", 2000)), Some(1));
+        assert_eq!(scores.insert(entry("Mid", 550)), Some(7));
+        assert_eq!(scores.entries.len(), HIGH_SCORE_COUNT);
+        assert_eq!(scores.entries.first().unwrap().name, "Top");
+        assert_eq!(scores.entries.last().unwrap().score, 300);
+    }
+
+    #[test]
+    fn combo_builds_and_caps() {
+        let mut scoring = Scoring::default();
+        let points: Vec<u32> = (0..MAX_COMBO + 2).map(|_| scoring.kill()).collect();
+
+        // The first kill starts the combo, the second is the first to build on it
+        let mut expected = vec![KILL_SCORE];
+        expected.extend((1..=MAX_COMBO).map(|multiplier| KILL_SCORE * multiplier));
+        expected.push(KILL_SCORE * MAX_COMBO);
+        assert_eq!(points, expected);
+        assert_eq!(scoring.multiplier, MAX_COMBO);
+    }
+
+    #[test]
+    fn combo_runs_out() {
+        let mut scoring = Scoring::default();
+        scoring.kill();
+        scoring.kill();
+        assert_eq!(scoring.multiplier, 2);
+
+        assert!(!scoring.tick(COMBO_WINDOW / 2.0));
+        assert_eq!(scoring.multiplier, 2);
+        assert!(scoring.tick(COMBO_WINDOW / 2.0));
+        assert_eq!(scoring.multiplier, 1);
+        // Only says so once
+        assert!(!scoring.tick(COMBO_WINDOW));
+
+        assert_eq!(scoring.kill(), KILL_SCORE);
+    }
+
+    #[test]
+    fn perfect_wave_gets_the_whole_bonus() {
+        let mut scoring = Scoring::default();
+        scoring.start_wave(30);
+        scoring.wave_shots = 10;
+        assert_eq!(scoring.wave_cleared(2, 40), WAVE_CLEAR_SCORE * 2 + ACCURACY_BONUS);
+    }
+
+    #[test]
+    fn misses_cut_the_bonus() {
+        let mut scoring = Scoring::default();
+        scoring.start_wave(0);
+        scoring.wave_shots = 8;
+        assert_eq!(scoring.wave_cleared(1, 6), WAVE_CLEAR_SCORE + ACCURACY_BONUS * 3 / 4);
+    }
+
+    #[test]
+    fn no_shots_no_bonus() {
+        let scoring = Scoring::default();
+        assert_eq!(scoring.wave_cleared(3, 0), WAVE_CLEAR_SCORE * 3);
+    }
+
+    #[test]
+    fn accuracy_is_capped_at_full() {
+        // Bullets still flying from the last wave can land after this one's shots are counted
+        let mut scoring = Scoring::default();
+        scoring.start_wave(0);
+        scoring.wave_shots = 2;
+        assert_eq!(scoring.wave_cleared(1, 5), WAVE_CLEAR_SCORE + ACCURACY_BONUS);
+    }
+}
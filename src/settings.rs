@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::{GAME_WIDTH, GAME_HEIGHT};
+use crate::utils::{config_dir, read_json, write_json};
+
+// Window sizes offered on the settings screen
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+// The window modes offered on the settings screen, in the order they cycle
+pub const WINDOW_MODES: [WindowMode; 3] = [
+    WindowMode::Windowed,
+    WindowMode::BorderlessFullscreen,
+    WindowMode::SizedFullscreen,
+];
+
+// Everything the player can change from the settings screen. Fields missing from an
+// older file keep their default.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub window_mode: WindowMode,
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    // Volumes run from 0 (silent) to 1 (full)
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+    pub blood: bool,
+    pub auto_reload: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_mode: WindowMode::Windowed,
+            resolution: (GAME_WIDTH as u32, GAME_HEIGHT as u32),
+            vsync: true,
+            master_volume: 0.8,
+            sfx_volume: 1.0,
+            music_volume: 0.6,
+            blood: true,
+            auto_reload: true,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        config_dir().join("settings.json")
+    }
+
+    // Like the controls, a broken file shouldn't stop the game starting
+    pub fn load() -> Settings {
+        let mut settings = match read_json::<Settings>(&Settings::path()) {
+            Ok(Some(settings)) => settings,
+            Ok(None) => return Settings::default(),
+            Err(err) => {
+                warn!("Couldn't read {}, using the default settings", err);
+                return Settings::default();
+            }
+        };
+
+        for volume in [&mut settings.master_volume, &mut settings.sfx_volume, &mut settings.music_volume] {
+            *volume = volume.clamp(0.0, 1.0);
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_json(&Settings::path(), self)
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+
+    // The window to open the game in
+    pub fn window(&self) -> Window {
+        Window {
+            title: "Zombie Game".to_string(),
+            resolution: (self.resolution.0 as f32, self.resolution.1 as f32).into(),
+            mode: self.window_mode,
+            present_mode: self.present_mode(),
+            ..default()
+        }
+    }
+
+    pub fn cycle_window_mode(&mut self) {
+        let current = WINDOW_MODES.iter().position(|mode| *mode == self.window_mode);
+        self.window_mode = WINDOW_MODES[current.map_or(0, |index| (index + 1) % WINDOW_MODES.len())];
+    }
+
+    pub fn cycle_resolution(&mut self) {
+        let current = RESOLUTIONS.iter().position(|resolution| *resolution == self.resolution);
+        self.resolution = RESOLUTIONS[current.map_or(0, |index| (index + 1) % RESOLUTIONS.len())];
+    }
+}
+
+// Push video settings to the window as they change. Only what actually changed is applied,
+// so turning the music down doesn't undo the player dragging the window to a new size.
+pub fn apply_window_settings(
+    settings: Res<Settings>,
+    mut applied: Local<Option<Settings>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+){
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    // The window was opened with these
+    let previous = applied.get_or_insert_with(|| settings.clone());
+
+    if settings.window_mode != previous.window_mode {
+        window.mode = settings.window_mode;
+    }
+    if settings.resolution != previous.resolution {
+        window.resolution.set(settings.resolution.0 as f32, settings.resolution.1 as f32);
+    }
+    if settings.vsync != previous.vsync {
+        window.present_mode = settings.present_mode();
+    }
+    *previous = settings.clone();
+}
@@ -0,0 +1,449 @@
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use bevy::window::WindowMode;
+use std::path::Path;
+
+use super::{despawn_screen, MainGameState};
+use crate::menu::{MenuState, TEXT_COLOR, BACKGROUND_COLOR, NORMAL_BUTTON, PRESSED_BUTTON};
+use crate::pause::PauseState;
+use crate::settings::Settings;
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Like the controls, the same screen is opened from the main menu and from the pause overlay
+            .add_systems(OnEnter(MenuState::Settings), settings_menu_setup)
+            .add_systems(OnEnter(PauseState::Settings), settings_menu_setup)
+            .add_systems(
+                Update,
+                (
+                    settings_menu_action,
+                    drag_sliders,
+                    save_settings.after(settings_menu_action).after(drag_sliders),
+                    update_setting_labels.after(save_settings),
+                ).run_if(in_state(MenuState::Settings).or_else(in_state(PauseState::Settings))),
+            )
+            .add_systems(OnExit(MenuState::Settings), despawn_screen::<OnSettingsMenuScreen>)
+            .add_systems(OnExit(PauseState::Settings), despawn_screen::<OnSettingsMenuScreen>);
+    }
+}
+
+#[derive(Component)]
+struct OnSettingsMenuScreen;
+
+// The settings that are changed by clicking through their values
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Choice {
+    WindowMode,
+    Resolution,
+    Vsync,
+    Blood,
+    AutoReload,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Volume {
+    Master,
+    Sfx,
+    Music,
+}
+
+#[derive(Component)]
+enum SettingsButtonAction {
+    Cycle(Choice),
+    Controls,
+    Back,
+}
+
+#[derive(Component)]
+struct ChoiceLabel(Choice);
+
+// The track of a volume slider, click or drag along it to set the volume
+#[derive(Component)]
+struct VolumeSlider(Volume);
+
+#[derive(Component)]
+struct SliderFill(Volume);
+
+#[derive(Component)]
+struct VolumeLabel(Volume);
+
+#[derive(Component)]
+struct StatusLabel;
+
+#[derive(Resource)]
+struct SettingsMenu {
+    // Saving waits until a slider is let go, rather than writing the file every frame of a drag
+    unsaved: bool,
+    status: String,
+}
+
+impl Volume {
+    fn get(self, settings: &Settings) -> f32 {
+        match self {
+            Volume::Master => settings.master_volume,
+            Volume::Sfx => settings.sfx_volume,
+            Volume::Music => settings.music_volume,
+        }
+    }
+
+    fn set(self, settings: &mut Settings, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match self {
+            Volume::Master => settings.master_volume = volume,
+            Volume::Sfx => settings.sfx_volume = volume,
+            Volume::Music => settings.music_volume = volume,
+        }
+    }
+}
+
+fn on_off(on: bool) -> String {
+    if on { "On" } else { "Off" }.to_string()
+}
+
+fn choice_label(choice: Choice, settings: &Settings) -> String {
+    match choice {
+        Choice::WindowMode => match settings.window_mode {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::BorderlessFullscreen => "Borderless",
+            WindowMode::SizedFullscreen | WindowMode::Fullscreen => "Fullscreen",
+        }.to_string(),
+        Choice::Resolution => format!("{} x {}", settings.resolution.0, settings.resolution.1),
+        Choice::Vsync => on_off(settings.vsync),
+        Choice::Blood => on_off(settings.blood),
+        Choice::AutoReload => on_off(settings.auto_reload),
+    }
+}
+
+fn settings_menu_setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+){
+    commands.insert_resource(SettingsMenu {
+        unsaved: false,
+        status: String::new(),
+    });
+
+    let font_path = Path::new("fonts").join("fira-sans.bold.ttf");
+    let font = asset_server.load(font_path);
+
+    let row_style = Style {
+        flex_direction: FlexDirection::Row,
+        align_items: AlignItems::Center,
+        margin: UiRect::vertical(Val::Px(4.0)),
+        ..default()
+    };
+    let name_style = Style {
+        width: Val::Px(200.0),
+        ..default()
+    };
+    let value_style = Style {
+        width: Val::Px(300.0),
+        height: Val::Px(36.0),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let small_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 26.0,
+        color: TEXT_COLOR,
+    };
+    let button_style = Style {
+        width: Val::Px(250.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: font.clone(),
+        font_size: 30.0,
+        color: TEXT_COLOR,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Auto),
+                padding: UiRect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BACKGROUND_COLOR.into(),
+            ..default()
+        })
+        .insert(OnSettingsMenuScreen)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Settings",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 50.0,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+
+            for (name, choice) in [
+                ("Window mode", Choice::WindowMode),
+                ("Resolution", Choice::Resolution),
+                ("Vsync", Choice::Vsync),
+            ] {
+                parent
+                    .spawn(NodeBundle { style: row_style.clone(), ..default() })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(name, small_text_style.clone()).with_style(name_style.clone()));
+                        row
+                            .spawn(ButtonBundle {
+                                style: value_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(SettingsButtonAction::Cycle(choice))
+                            .with_children(|button| {
+                                button
+                                    .spawn(TextBundle::from_section("", small_text_style.clone()))
+                                    .insert(ChoiceLabel(choice));
+                            });
+                    });
+            }
+
+            for (name, volume) in [
+                ("Master volume", Volume::Master),
+                ("Effects volume", Volume::Sfx),
+                ("Music volume", Volume::Music),
+            ] {
+                parent
+                    .spawn(NodeBundle { style: row_style.clone(), ..default() })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(name, small_text_style.clone()).with_style(name_style.clone()));
+                        row
+                            .spawn(ButtonBundle {
+                                style: Style {
+                                    height: Val::Px(20.0),
+                                    ..value_style.clone()
+                                },
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(VolumeSlider(volume))
+                            .insert(RelativeCursorPosition::default())
+                            .with_children(|track| {
+                                track
+                                    .spawn(NodeBundle {
+                                        style: Style {
+                                            position_type: PositionType::Absolute,
+                                            left: Val::Px(0.0),
+                                            height: Val::Percent(100.0),
+                                            ..default()
+                                        },
+                                        background_color: PRESSED_BUTTON.into(),
+                                        ..default()
+                                    })
+                                    .insert(SliderFill(volume));
+                            });
+                        row
+                            .spawn(
+                                TextBundle::from_section("", small_text_style.clone())
+                                    .with_style(Style {
+                                        width: Val::Px(70.0),
+                                        margin: UiRect::left(Val::Px(10.0)),
+                                        ..default()
+                                    }),
+                            )
+                            .insert(VolumeLabel(volume));
+                    });
+            }
+
+            for (name, choice) in [
+                ("Blood", Choice::Blood),
+                ("Auto reload", Choice::AutoReload),
+            ] {
+                parent
+                    .spawn(NodeBundle { style: row_style.clone(), ..default() })
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(name, small_text_style.clone()).with_style(name_style.clone()));
+                        row
+                            .spawn(ButtonBundle {
+                                style: value_style.clone(),
+                                background_color: NORMAL_BUTTON.into(),
+                                ..default()
+                            })
+                            .insert(SettingsButtonAction::Cycle(choice))
+                            .with_children(|button| {
+                                button
+                                    .spawn(TextBundle::from_section("", small_text_style.clone()))
+                                    .insert(ChoiceLabel(choice));
+                            });
+                    });
+            }
+
+            parent
+                .spawn(
+                    TextBundle::from_section("", small_text_style.clone())
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        }),
+                )
+                .insert(StatusLabel);
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    row
+                        .spawn(ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(SettingsButtonAction::Controls)
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section("Controls", button_text_style.clone()));
+                        });
+
+                    row
+                        .spawn(ButtonBundle {
+                            style: button_style.clone(),
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        })
+                        .insert(SettingsButtonAction::Back)
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section("Back", button_text_style.clone()));
+                        });
+                });
+        });
+}
+
+fn settings_menu_action(
+    interaction_query: Query<
+        (&Interaction, &SettingsButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    game_state: Res<State<MainGameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+){
+    // Escape backs out the same as the button
+    let mut back = keys.just_pressed(KeyCode::Escape);
+    let mut controls = false;
+
+    for (interaction, button_action) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button_action {
+            SettingsButtonAction::Cycle(choice) => {
+                match choice {
+                    Choice::WindowMode => settings.cycle_window_mode(),
+                    Choice::Resolution => settings.cycle_resolution(),
+                    Choice::Vsync => settings.vsync = !settings.vsync,
+                    Choice::Blood => settings.blood = !settings.blood,
+                    Choice::AutoReload => settings.auto_reload = !settings.auto_reload,
+                }
+                settings_menu.unsaved = true;
+            }
+            SettingsButtonAction::Controls => controls = true,
+            SettingsButtonAction::Back => back = true,
+        }
+    }
+
+    // Back to wherever the screen was opened from
+    let in_game = *game_state.get() == MainGameState::Game;
+    if controls {
+        if in_game {
+            pause_state.set(PauseState::Controls);
+        } else {
+            menu_state.set(MenuState::Controls);
+        }
+    } else if back {
+        if in_game {
+            pause_state.set(PauseState::Paused);
+        } else {
+            menu_state.set(MenuState::Main);
+        }
+    }
+}
+
+fn drag_sliders(
+    sliders: Query<(&Interaction, &RelativeCursorPosition, &VolumeSlider)>,
+    mut settings: ResMut<Settings>,
+    mut settings_menu: ResMut<SettingsMenu>,
+){
+    for (interaction, cursor, VolumeSlider(volume)) in sliders.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        // Snap to whole percents so the label and the file agree
+        let value = (position.x.clamp(0.0, 1.0) * 100.0).round() / 100.0;
+        if value != volume.get(&settings) {
+            volume.set(&mut settings, value);
+            settings_menu.unsaved = true;
+        }
+    }
+}
+
+fn save_settings(
+    sliders: Query<&Interaction, With<VolumeSlider>>,
+    settings: Res<Settings>,
+    mut settings_menu: ResMut<SettingsMenu>,
+){
+    let dragging = sliders.iter().any(|interaction| *interaction == Interaction::Pressed);
+    if !settings_menu.unsaved || dragging {
+        return;
+    }
+    settings_menu.unsaved = false;
+    settings_menu.status = match settings.save() {
+        Ok(()) => String::new(),
+        Err(err) => format!("Couldn't save settings: {}", err),
+    };
+}
+
+fn update_setting_labels(
+    settings: Res<Settings>,
+    settings_menu: Res<SettingsMenu>,
+    mut choices: Query<(&ChoiceLabel, &mut Text), (Without<VolumeLabel>, Without<StatusLabel>)>,
+    mut volumes: Query<(&VolumeLabel, &mut Text), (Without<ChoiceLabel>, Without<StatusLabel>)>,
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    mut status: Query<&mut Text, (With<StatusLabel>, Without<ChoiceLabel>, Without<VolumeLabel>)>,
+){
+    if !settings.is_changed() && !settings_menu.is_changed() {
+        return;
+    }
+
+    for (ChoiceLabel(choice), mut text) in choices.iter_mut() {
+        text.sections[0].value = choice_label(*choice, &settings);
+    }
+    for (VolumeLabel(volume), mut text) in volumes.iter_mut() {
+        text.sections[0].value = format!("{}%", (volume.get(&settings) * 100.0).round());
+    }
+    for (SliderFill(volume), mut style) in fills.iter_mut() {
+        style.width = Val::Percent(volume.get(&settings) * 100.0);
+    }
+    for mut text in status.iter_mut() {
+        text.sections[0].value = settings_menu.status.clone();
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use bevy::audio::{AudioSinkPlayback, PlaybackMode, Volume};
+use rand::Rng;
+use std::path::Path;
+
+use super::MainGameState;
+use crate::pause::PauseState;
+use crate::player::Player;
+use crate::settings::Settings;
+use crate::zombie::Zombie;
+use crate::rng::GameRng;
+
+// Sounds further from the player than this can't be heard
+const HEARING_RANGE: f32 = 1200.0;
+// Anything this close plays at full volume
+const FULL_VOLUME_RANGE: f32 = 200.0;
+// Seconds for one music track to fade into the next
+const CROSSFADE_TIME: f32 = 1.5;
+// Seconds between one zombie groan and the next, for the whole horde
+const GROAN_INTERVAL: (f32, f32) = (1.5, 4.0);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Sound {
+    Shot,
+    Hit,
+    Groan,
+    ZombieDeath,
+    PlayerHurt,
+}
+
+impl Sound {
+    fn file(self) -> &'static str {
+        match self {
+            Sound::Shot => "shot.wav",
+            Sound::Hit => "hit.wav",
+            Sound::Groan => "groan.wav",
+            Sound::ZombieDeath => "zombie_death.wav",
+            Sound::PlayerHurt => "player_hurt.wav",
+        }
+    }
+}
+
+// Send one of these to play a sound effect. Sounds with a position get quieter the
+// further they are from the player.
+#[derive(Event)]
+pub struct PlaySound {
+    pub sound: Sound,
+    pub at: Option<Vec2>,
+}
+
+impl PlaySound {
+    pub fn at(sound: Sound, at: Vec2) -> Self {
+        PlaySound { sound: sound, at: Some(at) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MusicTrack {
+    Menu,
+    Game,
+}
+
+impl MusicTrack {
+    fn file(self) -> &'static str {
+        match self {
+            MusicTrack::Menu => "menu_music.wav",
+            MusicTrack::Game => "game_music.wav",
+        }
+    }
+}
+
+// A playing music track and how far it has faded in, from 0 to 1
+#[derive(Component)]
+struct Music {
+    track: MusicTrack,
+    fade: f32,
+    fading_out: bool,
+}
+
+// With audio off the events are still there for the game to send, they just go nowhere,
+// so nothing needs a sound card, e.g. on a headless machine
+pub struct SoundPlugin {
+    pub enabled: bool,
+}
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySound>();
+        if !self.enabled {
+            return;
+        }
+        app
+            .add_systems(Update, (
+                play_sounds,
+                zombie_groans.run_if(in_state(MainGameState::Game).and_then(in_state(PauseState::Running))),
+                play_music,
+            ));
+    }
+}
+
+fn sound_path(file: &str) -> std::path::PathBuf {
+    Path::new("sounds").join(file)
+}
+
+// Falls away in a straight line from full volume to silence at the edge of hearing
+fn distance_volume(distance: f32) -> f32 {
+    1.0 - ((distance - FULL_VOLUME_RANGE) / (HEARING_RANGE - FULL_VOLUME_RANGE)).clamp(0.0, 1.0)
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut sounds: EventReader<PlaySound>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    players: Query<&Player>,
+){
+    let listener = players.get_single().ok().map(|player| player.loc);
+
+    for sound in sounds.iter() {
+        let distance = match (sound.at, listener) {
+            (Some(at), Some(listener)) => at.distance(listener),
+            _ => 0.0,
+        };
+        let volume = settings.master_volume * settings.sfx_volume * distance_volume(distance);
+        if volume <= 0.0 {
+            continue;
+        }
+
+        commands.spawn(AudioBundle {
+            source: asset_server.load(sound_path(sound.sound.file())),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Despawn,
+                volume: Volume::new_relative(volume),
+                ..default()
+            },
+        });
+    }
+}
+
+// Every so often one of the zombies lets the player know it's there
+fn zombie_groans(
+    time: Res<Time>,
+    mut next_groan: Local<f32>,
+    zombies: Query<&Zombie>,
+    mut sounds: EventWriter<PlaySound>,
+    mut game_rng: ResMut<GameRng>,
+){
+    *next_groan -= time.delta_seconds();
+    if *next_groan > 0.0 {
+        return;
+    }
+    let rng = game_rng.cosmetic();
+    *next_groan = rng.gen_range(GROAN_INTERVAL.0..GROAN_INTERVAL.1);
+
+    let count = zombies.iter().count();
+    if count == 0 {
+        return;
+    }
+    if let Some(zombie) = zombies.iter().nth(rng.gen_range(0..count)) {
+        sounds.send(PlaySound::at(Sound::Groan, zombie.pos));
+    }
+}
+
+// Fades in the track for the current screen while fading out whatever was playing before
+fn play_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    game_state: Res<State<MainGameState>>,
+    mut music: Query<(Entity, &mut Music, Option<&AudioSink>)>,
+){
+    let wanted = match game_state.get() {
+        MainGameState::Menu => MusicTrack::Menu,
+        MainGameState::Game | MainGameState::Restarting | MainGameState::GameOver => MusicTrack::Game,
+    };
+
+    if !music.iter().any(|(_, playing, _)| playing.track == wanted && !playing.fading_out) {
+        for (_, mut playing, _) in music.iter_mut() {
+            playing.fading_out = true;
+        }
+        commands
+            .spawn(AudioBundle {
+                source: asset_server.load(sound_path(wanted.file())),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new_relative(0.0),
+                    ..default()
+                },
+            })
+            .insert(Music {
+                track: wanted,
+                fade: 0.0,
+                fading_out: false,
+            });
+    }
+
+    // Real time, so the music keeps going while the game is paused
+    let step = time.raw_delta_seconds() / CROSSFADE_TIME;
+    for (entity, mut playing, sink) in music.iter_mut() {
+        playing.fade = if playing.fading_out {
+            playing.fade - step
+        } else {
+            playing.fade + step
+        }.clamp(0.0, 1.0);
+
+        if playing.fading_out && playing.fade <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // The sink turns up once the track has loaded and started
+        if let Some(sink) = sink {
+            sink.set_volume(settings.master_volume * settings.music_volume * playing.fade);
+        }
+    }
+}
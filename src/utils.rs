@@ -1,3 +1,11 @@
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::collsion_objects::CollisionObject;
+
 pub fn normalize_angle(angle: f32) -> f32 {
     // Make sure angle is between 0.0 and 2.0 * PI
     if angle < 0. {
@@ -9,6 +17,226 @@ pub fn normalize_angle(angle: f32) -> f32 {
     }
 }
 
-// pub fn check_position_move(start: Vec2, next_move: Vec2, objects: Query ) -> Vec2 {
+// Work out where something at `start` ends up after trying to move by `next_move`.
+// If the full move is blocked we try each axis on its own so movers slide along the obstacle.
+pub fn check_position_move(start: Vec2, next_move: Vec2, hit_box: Vec2, objects: &Query<&CollisionObject>) -> Vec2 {
+    let blocked = |pos: Vec2| objects.iter().any(|object| object.overlaps(pos, hit_box));
+
+    let target = start + next_move;
+    if !blocked(target) {
+        return target;
+    }
+
+    // Already stuck inside something (e.g. spawned there) - let it walk back out, but not further in
+    if blocked(start) {
+        let overlap = |pos: Vec2| objects.iter().map(|object| object.overlap_area(pos, hit_box)).sum::<f32>();
+        return if overlap(target) < overlap(start) { target } else { start };
+    }
+
+    let slide_x = Vec2::new(target.x, start.y);
+    if next_move.x != 0.0 && !blocked(slide_x) {
+        return slide_x;
+    }
+
+    let slide_y = Vec2::new(start.x, target.y);
+    if next_move.y != 0.0 && !blocked(slide_y) {
+        return slide_y;
+    }
+
+    start
+}
+
+// Game data lives in the asset folder, resolved the same way Bevy finds it
+pub fn assets_dir() -> PathBuf {
+    let base = match std::env::var("CARGO_MANIFEST_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+            .unwrap_or_default(),
+    };
+    base.join("assets")
+}
+
+// Where per-user files such as key bindings are kept
+pub fn config_dir() -> PathBuf {
+    let env_dir = |name: &str| std::env::var_os(name).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    };
+    base.unwrap_or_default().join("zombie-game-bevy")
+}
+
+// Reads one of the files in the config folder. A missing file just hasn't been written yet, so
+// that's None, anything else comes back as what went wrong with the path in front.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+    serde_json::from_str(&source)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+#[derive(Debug)]
+pub enum VersionedJsonError {
+    Io(PathBuf, std::io::Error),
+    Invalid(PathBuf, String),
+    // The version in the file, and the one this game reads
+    Version(PathBuf, u32, u32),
+}
+
+impl fmt::Display for VersionedJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VersionedJsonError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            VersionedJsonError::Invalid(path, reason) => write!(f, "{}: {}", path.display(), reason),
+            VersionedJsonError::Version(path, found, expected) if found > expected => write!(
+                f, "{} is from a newer version of the game (version {}, this game reads {})",
+                path.display(), found, expected
+            ),
+            VersionedJsonError::Version(path, found, _) => write!(
+                f, "{} is from an older version of the game that can no longer be read (version {})",
+                path.display(), found
+            ),
+        }
+    }
+}
+
+// Only the version is read first, so a file in another format gets a clear message
+// rather than whatever field happens to be missing
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+// Reads a file the game writes with a "version" field, such as a save or a replay, turning it
+// away unless it is the version given
+pub fn read_versioned_json<T: DeserializeOwned>(path: &Path, version: u32) -> Result<T, VersionedJsonError> {
+    let source = std::fs::read_to_string(path).map_err(|err| VersionedJsonError::Io(path.to_path_buf(), err))?;
+    parse_versioned_json(path, &source, version)
+}
+
+pub fn parse_versioned_json<T: DeserializeOwned>(path: &Path, source: &str, version: u32) -> Result<T, VersionedJsonError> {
+    let invalid = |err: serde_json::Error| VersionedJsonError::Invalid(path.to_path_buf(), err.to_string());
+    let header = serde_json::from_str::<VersionHeader>(source).map_err(invalid)?;
+    if header.version != version {
+        return Err(VersionedJsonError::Version(path.to_path_buf(), header.version, version));
+    }
+    serde_json::from_str::<T>(source).map_err(invalid)
+}
 
-// }
\ No newline at end of file
+// Pretty printed, so the file can be read and fixed by hand
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    write_json_with(path, || serde_json::to_string_pretty(value))
+}
+
+// All on one line, for files too long to read by hand anyway
+pub fn write_json_compact<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    write_json_with(path, || serde_json::to_string(value))
+}
+
+fn write_json_with(path: &Path, to_json: impl FnOnce() -> serde_json::Result<String>) -> Result<(), String> {
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, to_json()? + "\n")
+    };
+    write().map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("zombie-game-bevy-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let path = temp_path("round_trip.json");
+        let value = BTreeMap::from([("wave".to_string(), 3), ("score".to_string(), 1200)]);
+
+        write_json(&path, &value).unwrap();
+        assert_eq!(read_json::<BTreeMap<String, i32>>(&path), Ok(Some(value)));
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("}\n"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_json_file() {
+        assert_eq!(read_json::<Vec<i32>>(&temp_path("missing.json")), Ok(None));
+    }
+
+    #[test]
+    fn broken_json_file() {
+        let path = temp_path("broken.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "[1, 2").unwrap();
+
+        let err = read_json::<Vec<i32>>(&path).unwrap_err();
+        assert!(err.starts_with(&format!("{}: ", path.display())), "{}", err);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Versioned {
+        version: u32,
+        wave: u32,
+    }
+
+    fn parse(source: &str) -> Result<Versioned, String> {
+        parse_versioned_json(Path::new("run.json"), source, 2).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn versioned_json() {
+        assert_eq!(parse(r#"{"version": 2, "wave": 4}"#), Ok(Versioned { version: 2, wave: 4 }));
+    }
+
+    #[test]
+    fn other_versions_are_turned_away() {
+        // Only the version is looked at, whatever else is in there
+        assert_eq!(
+            parse(r#"{"version": 3, "wave": "four"}"#),
+            Err("run.json is from a newer version of the game (version 3, this game reads 2)".to_string())
+        );
+        assert_eq!(
+            parse(r#"{"version": 1}"#),
+            Err("run.json is from an older version of the game that can no longer be read (version 1)".to_string())
+        );
+        assert!(matches!(
+            parse_versioned_json::<Versioned>(Path::new("run.json"), r#"{"version": 1}"#, 2),
+            Err(VersionedJsonError::Version(_, 1, 2))
+        ));
+    }
+
+    #[test]
+    fn broken_versioned_json() {
+        let err = parse(r#"{"wave": 4}"#).unwrap_err();
+        assert!(err.starts_with("run.json: missing field `version`"), "{}", err);
+
+        // The right version, but not the right contents
+        let err = parse(r#"{"version": 2}"#).unwrap_err();
+        assert!(err.starts_with("run.json: missing field `wave`"), "{}", err);
+    }
+
+    #[test]
+    fn missing_versioned_json() {
+        let err = read_versioned_json::<Versioned>(&temp_path("missing_versioned.json"), 2).unwrap_err();
+        assert!(matches!(err, VersionedJsonError::Io(..)), "{}", err);
+    }
+}
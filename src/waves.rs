@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::level::{LevelTiles, level_origin, point_to_vec};
+use crate::utils::assets_dir;
+use crate::zombie::{self, Zombie};
+
+// Shortest gap allowed between spawns, however far the difficulty has ramped
+const MIN_SPAWN_DELAY: f32 = 0.2;
+
+// How tough a kind of zombie is, looked up by name from the wave definitions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EnemyType {
+    pub health: i32,
+    pub speed: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WaveDefinition {
+    pub count: u32,
+    // Zombie types spawned in turn, e.g. ["zombie", "zombie", "runner"]
+    pub types: Vec<String>,
+    // Seconds between each zombie appearing
+    pub spawn_delay: f32,
+}
+
+// Applied on top of the wave definitions so each wave is harder than the last
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WaveRamp {
+    // Extra zombies per wave once the defined waves have run out
+    pub count_per_wave: f32,
+    pub health_per_wave: i32,
+    pub speed_multiplier: f32,
+    pub delay_multiplier: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WaveConfig {
+    // Seconds before the first wave and between waves
+    pub start_delay: f32,
+    pub break_time: f32,
+    // In name order, so a replay writes them out the same way every time
+    pub enemy_types: BTreeMap<String, EnemyType>,
+    pub waves: Vec<WaveDefinition>,
+    pub ramp: WaveRamp,
+}
+
+#[derive(Debug)]
+pub enum WaveConfigError {
+    Io(PathBuf, std::io::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for WaveConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaveConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            WaveConfigError::Invalid(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl WaveConfig {
+    pub fn path() -> PathBuf {
+        assets_dir().join("waves.json")
+    }
+
+    pub fn load() -> Result<WaveConfig, WaveConfigError> {
+        let path = WaveConfig::path();
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| WaveConfigError::Io(path.clone(), err))?;
+        let config: WaveConfig = serde_json::from_str(&source)
+            .map_err(|err| WaveConfigError::Invalid(path.clone(), err.to_string()))?;
+        config.validate()
+            .map_err(|message| WaveConfigError::Invalid(path.clone(), message))?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.waves.is_empty() {
+            return Err("no waves defined".to_string());
+        }
+        for (index, wave) in self.waves.iter().enumerate() {
+            if wave.types.is_empty() {
+                return Err(format!("wave {} has no zombie types", index + 1));
+            }
+            if let Some(unknown) = wave.types.iter().find(|name| !self.enemy_types.contains_key(*name)) {
+                return Err(format!("wave {} uses unknown zombie type \"{}\"", index + 1, unknown));
+            }
+            if wave.spawn_delay < 0.0 {
+                return Err(format!("wave {} has a negative spawn delay", index + 1));
+            }
+        }
+        Ok(())
+    }
+
+    // The definition for a wave number (starting at 1), ramped up for how far in we are
+    pub fn wave(&self, number: u32) -> WaveDefinition {
+        let index = (number as usize - 1).min(self.waves.len() - 1);
+        let mut wave = self.waves[index].clone();
+
+        let past_defined = (number as usize).saturating_sub(self.waves.len());
+        wave.count += (self.ramp.count_per_wave * past_defined as f32).round() as u32;
+        wave.spawn_delay = (wave.spawn_delay * self.ramp.delay_multiplier.powi(number as i32 - 1))
+            .max(MIN_SPAWN_DELAY);
+        wave
+    }
+
+    pub fn enemy(&self, name: &str, wave: u32) -> EnemyType {
+        let mut enemy = self.enemy_types[name].clone();
+        enemy.health += self.ramp.health_per_wave * (wave as i32 - 1);
+        enemy.speed *= self.ramp.speed_multiplier.powi(wave as i32 - 1);
+        enemy
+    }
+}
+
+// Where zombies come from, taken from the enemies listed in each level tile
+#[derive(Clone, Debug)]
+pub struct SpawnPoint {
+    pub description: String,
+    pub route: Vec<Vec2>,
+}
+
+pub fn spawn_points(tiles: &LevelTiles) -> Vec<SpawnPoint> {
+    let mut points = Vec::<SpawnPoint>::new();
+    for level in tiles.levels.values() {
+        let origin = level_origin(level);
+        for enemy in level.enemies.iter() {
+            // Spawn at the location, then patrol the route (if there is one)
+            let mut route = vec![origin + point_to_vec(enemy.location)];
+            route.extend(enemy.route.iter().map(|point| origin + point_to_vec(*point)));
+            points.push(SpawnPoint {
+                description: enemy.description.clone(),
+                route: route,
+            });
+        }
+    }
+    points
+}
+
+#[derive(Event)]
+pub struct WaveStarted {
+    pub wave: u32,
+    pub zombies: u32,
+    // Sent again when a saved run is continued, to put the wave back on the HUD without starting it over
+    pub continued: bool,
+}
+
+#[derive(Event)]
+pub struct WaveCleared {
+    pub wave: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WavePhase {
+    // Waiting for the next wave to start
+    Break,
+    Spawning,
+    // Everything is out, waiting for the player to finish them off
+    Fighting,
+}
+
+#[derive(Resource)]
+pub struct WaveDirector {
+    pub config: WaveConfig,
+    pub spawn_points: Vec<SpawnPoint>,
+    pub wave: u32,
+    pub phase: WavePhase,
+    pub timer: Timer,
+    // Zombie types still to come this wave, in spawn order
+    pub to_spawn: Vec<String>,
+    // The spawn point to try first for the next zombie
+    pub next_point: usize,
+    texture_atlas: Option<Handle<TextureAtlas>>,
+}
+
+impl WaveDirector {
+    pub fn new(config: WaveConfig, tiles: &LevelTiles) -> Self {
+        let spawn_points = spawn_points(tiles);
+        if spawn_points.is_empty() {
+            warn!("No enemies in any level tile, so there is nowhere for zombies to spawn");
+        }
+        WaveDirector {
+            timer: Timer::from_seconds(config.start_delay, TimerMode::Once),
+            config: config,
+            spawn_points: spawn_points,
+            wave: 0,
+            phase: WavePhase::Break,
+            to_spawn: Vec::new(),
+            next_point: 0,
+            texture_atlas: None,
+        }
+    }
+
+    fn start_wave(&mut self) -> WaveStarted {
+        self.wave += 1;
+        let wave = self.config.wave(self.wave);
+        self.to_spawn = (0..wave.count as usize)
+            .map(|index| wave.types[index % wave.types.len()].clone())
+            .rev()
+            .collect();
+        self.phase = WavePhase::Spawning;
+        // First zombie straight away, then one every spawn delay
+        self.timer = Timer::from_seconds(wave.spawn_delay, TimerMode::Repeating);
+        self.timer.set_elapsed(self.timer.duration());
+
+        WaveStarted {
+            wave: self.wave,
+            zombies: wave.count,
+            continued: false,
+        }
+    }
+
+    // Take turns around the spawn points, preferring ones meant for this type of zombie
+    fn next_spawn_point(&mut self, description: &str) -> Option<&SpawnPoint> {
+        if self.spawn_points.is_empty() {
+            return None;
+        }
+        let count = self.spawn_points.len();
+        let matching = (0..count)
+            .map(|step| (self.next_point + step) % count)
+            .find(|index| self.spawn_points[*index].description == description);
+        let index = matching.unwrap_or(self.next_point % count);
+        self.next_point = index + 1;
+        Some(&self.spawn_points[index])
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn wave_director(
+    mut commands: Commands,
+    time: Res<FixedTime>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut director: ResMut<WaveDirector>,
+    zombies: Query<&Zombie>,
+    mut started: EventWriter<WaveStarted>,
+    mut cleared: EventWriter<WaveCleared>,
+){
+    if director.spawn_points.is_empty() {
+        return;
+    }
+    director.timer.tick(time.period);
+
+    match director.phase {
+        WavePhase::Break => {
+            if director.timer.finished() {
+                let event = director.start_wave();
+                started.send(event);
+            }
+        }
+        WavePhase::Spawning => {
+            for _ in 0..director.timer.times_finished_this_tick() {
+                let Some(description) = director.to_spawn.pop() else {
+                    break;
+                };
+                let wave = director.wave;
+                let enemy = director.config.enemy(&description, wave);
+                let route = director.next_spawn_point(&description).unwrap().route.clone();
+
+                let texture_atlas = director.texture_atlas.get_or_insert_with(|| {
+                    zombie::zombie_texture_atlas(&asset_server, &mut texture_atlases)
+                }).clone();
+
+                let mut zombie = Zombie::new(route.clone(), 1 % route.len());
+                zombie.health = enemy.health;
+                zombie.speed = enemy.speed;
+                zombie::spawn_zombie(&mut commands, texture_atlas, zombie);
+            }
+            if director.to_spawn.is_empty() {
+                director.phase = WavePhase::Fighting;
+            }
+        }
+        WavePhase::Fighting => {
+            if zombies.is_empty() {
+                cleared.send(WaveCleared {
+                    wave: director.wave,
+                });
+                director.phase = WavePhase::Break;
+                director.timer = Timer::from_seconds(director.config.break_time, TimerMode::Once);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WaveConfig {
+        serde_json::from_str(r#"{
+            "start_delay": 2.0,
+            "break_time": 5.0,
+            "enemy_types": {
+                "zombie": {"health": 5, "speed": 100.0},
+                "runner": {"health": 3, "speed": 200.0}
+            },
+            "waves": [
+                {"count": 2, "types": ["zombie"], "spawn_delay": 1.0},
+                {"count": 4, "types": ["zombie", "runner"], "spawn_delay": 0.8}
+            ],
+            "ramp": {
+                "count_per_wave": 1.5,
+                "health_per_wave": 2,
+                "speed_multiplier": 1.1,
+                "delay_multiplier": 0.5
+            }
+        }"#).unwrap()
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn first_wave_is_as_defined() {
+        let wave = config().wave(1);
+        assert_eq!(wave.count, 2);
+        assert_eq!(wave.types, vec!["zombie"]);
+        assert_near(wave.spawn_delay, 1.0);
+    }
+
+    #[test]
+    fn defined_waves_only_speed_up() {
+        let wave = config().wave(2);
+        assert_eq!(wave.count, 4);
+        assert_eq!(wave.types, vec!["zombie", "runner"]);
+        assert_near(wave.spawn_delay, 0.4);
+    }
+
+    #[test]
+    fn waves_past_the_last_repeat_it_with_more_zombies() {
+        let config = config();
+        let third = config.wave(3);
+        assert_eq!(third.types, vec!["zombie", "runner"]);
+        // 1.5 extra rounds up to 2
+        assert_eq!(third.count, 6);
+        assert_eq!(config.wave(4).count, 7);
+        assert_eq!(config.wave(12).count, 19);
+    }
+
+    #[test]
+    fn spawn_delay_never_drops_below_the_floor() {
+        let config = config();
+        assert_near(config.wave(3).spawn_delay, MIN_SPAWN_DELAY);
+        assert_near(config.wave(30).spawn_delay, MIN_SPAWN_DELAY);
+    }
+
+    #[test]
+    fn first_wave_enemies_are_as_defined() {
+        let zombie = config().enemy("zombie", 1);
+        assert_eq!(zombie.health, 5);
+        assert_near(zombie.speed, 100.0);
+    }
+
+    #[test]
+    fn enemies_toughen_every_wave() {
+        let config = config();
+        let runner = config.enemy("runner", 3);
+        assert_eq!(runner.health, 7);
+        assert_near(runner.speed, 242.0);
+
+        // Keeps going past the defined waves
+        let zombie = config.enemy("zombie", 6);
+        assert_eq!(zombie.health, 15);
+        assert_near(zombie.speed, 100.0 * 1.1f32.powi(5));
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(config().validate(), Ok(()));
+
+        let mut config = config();
+        config.waves[1].types.push("crawler".to_string());
+        assert_eq!(config.validate(), Err("wave 2 uses unknown zombie type \"crawler\"".to_string()));
+
+        config.waves[1].types.clear();
+        assert_eq!(config.validate(), Err("wave 2 has no zombie types".to_string()));
+
+        config.waves.clear();
+        assert_eq!(config.validate(), Err("no waves defined".to_string()));
+    }
+}
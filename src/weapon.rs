@@ -0,0 +1,223 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::player::Player;
+use crate::inventory::Inventory;
+use crate::controls::PlayerInput;
+use crate::hud::AmmoChanged;
+
+// Which ammo a gun takes from the inventory
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Caliber {
+    NineMil,
+    TwelveGauge,
+}
+
+// Everything that makes one gun feel different from another
+#[derive(Debug)]
+pub struct Weapon {
+    pub name: &'static str,
+    pub caliber: Caliber,
+    // Shots per second
+    pub fire_rate: f32,
+    // Keep firing while the button is held, rather than once per click
+    pub automatic: bool,
+    // Bullets per shot, each in its own slice of the cone
+    pub pellets: u32,
+    // Width of the cone in radians
+    pub spread: f32,
+    pub magazine: u32,
+    // Seconds to put in a fresh magazine
+    pub reload_time: f32,
+    pub bullet_speed: f32,
+    pub damage: i32,
+}
+
+pub const PISTOL: Weapon = Weapon {
+    name: "Pistol",
+    caliber: Caliber::NineMil,
+    fire_rate: 4.0,
+    automatic: false,
+    pellets: 1,
+    spread: 0.03,
+    magazine: 12,
+    reload_time: 1.0,
+    bullet_speed: 600.0,
+    damage: 2,
+};
+
+pub const SHOTGUN: Weapon = Weapon {
+    name: "Shotgun",
+    caliber: Caliber::TwelveGauge,
+    fire_rate: 1.2,
+    automatic: false,
+    pellets: 6,
+    spread: 0.5,
+    magazine: 6,
+    reload_time: 2.0,
+    bullet_speed: 550.0,
+    damage: 1,
+};
+
+pub const SMG: Weapon = Weapon {
+    name: "SMG",
+    caliber: Caliber::NineMil,
+    fire_rate: 12.0,
+    automatic: true,
+    pellets: 1,
+    spread: 0.12,
+    magazine: 30,
+    reload_time: 1.6,
+    bullet_speed: 700.0,
+    damage: 1,
+};
+
+// Seconds to get a gun out before it can fire
+const SWAP_TIME: f32 = 0.3;
+
+// In number key order
+pub const WEAPONS: [&Weapon; 3] = [&PISTOL, &SHOTGUN, &SMG];
+
+// A gun the player is carrying and what state it is in
+pub struct WeaponSlot {
+    pub weapon: &'static Weapon,
+    pub in_magazine: u32,
+    // Seconds left until the reload finishes, zero when not reloading
+    pub reloading: f32,
+}
+
+impl WeaponSlot {
+    pub fn new(weapon: &'static Weapon) -> Self {
+        WeaponSlot {
+            weapon: weapon,
+            in_magazine: weapon.magazine,
+            reloading: 0.0,
+        }
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reloading > 0.0
+    }
+
+    // Directions for each pellet of a shot aimed at angle, randomised within its slice of the cone
+    pub fn pellet_angles(&self, angle: f32, rng: &mut impl Rng) -> Vec<f32> {
+        let slice = self.weapon.spread / self.weapon.pellets as f32;
+        (0..self.weapon.pellets)
+            .map(|pellet| {
+                let centre = -self.weapon.spread / 2.0 + slice * (pellet as f32 + 0.5);
+                angle + centre + rng.gen_range(-0.5..=0.5) * slice
+            })
+            .collect()
+    }
+
+    // Only worth reloading with a gap in the magazine and something in reserve to fill it
+    pub fn start_reload(&mut self, reserve: u32) {
+        if !self.is_reloading() && self.in_magazine < self.weapon.magazine && reserve > 0 {
+            self.reloading = self.weapon.reload_time;
+        }
+    }
+}
+
+// The guns the player is carrying, which one is out, and when it can next fire
+pub struct Loadout {
+    pub slots: Vec<WeaponSlot>,
+    pub current: usize,
+    // Seconds until the current weapon can fire again
+    pub cooldown: f32,
+}
+
+impl Loadout {
+    pub fn new(weapons: &[&'static Weapon]) -> Self {
+        Loadout {
+            slots: weapons.iter().copied().map(WeaponSlot::new).collect(),
+            current: 0,
+            cooldown: 0.0,
+        }
+    }
+
+    pub fn carries(&self, weapon: &Weapon) -> bool {
+        self.slots.iter().any(|slot| slot.weapon.name == weapon.name)
+    }
+
+    // New guns come loaded and go on the end, so the number keys of the others don't change
+    pub fn add(&mut self, weapon: &'static Weapon) {
+        if !self.carries(weapon) {
+            self.slots.push(WeaponSlot::new(weapon));
+        }
+    }
+
+    pub fn current(&self) -> &WeaponSlot {
+        &self.slots[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut WeaponSlot {
+        &mut self.slots[self.current]
+    }
+
+    // Swapping guns abandons a reload part way through. The last gun's fire rate still has to run
+    // out, so flicking between guns isn't a way round it
+    pub fn select(&mut self, index: usize) {
+        if index == self.current || index >= self.slots.len() {
+            return;
+        }
+        self.current_mut().reloading = 0.0;
+        self.current = index;
+        self.cooldown = self.cooldown.max(SWAP_TIME);
+    }
+
+    pub fn cycle(&mut self, step: i32) {
+        let count = self.slots.len() as i32;
+        let index = (self.current as i32 + step).rem_euclid(count);
+        self.select(index as usize);
+    }
+}
+
+pub fn weapon_switch(
+    input: Res<PlayerInput>,
+    mut players: Query<&mut Inventory, With<Player>>,
+    mut ammo: EventWriter<AmmoChanged>,
+){
+    if players.is_empty() || (input.select_weapon.is_none() && input.cycle_weapon == 0) {
+        return;
+    }
+    let mut inventory = players.single_mut();
+    let loadout = &mut inventory.loadout;
+
+    if let Some(index) = input.select_weapon {
+        loadout.select(index);
+    }
+    if input.cycle_weapon != 0 {
+        loadout.cycle(input.cycle_weapon);
+    }
+    ammo.send(AmmoChanged::new(&inventory));
+}
+
+pub fn weapon_reload(
+    time: Res<FixedTime>,
+    input: Res<PlayerInput>,
+    mut players: Query<&mut Inventory, With<Player>>,
+    mut ammo: EventWriter<AmmoChanged>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let mut inventory = players.single_mut();
+
+    let loadout = &mut inventory.loadout;
+    loadout.cooldown = (loadout.cooldown - time.period.as_secs_f32()).max(0.0);
+
+    if input.reload {
+        inventory.start_reload();
+        ammo.send(AmmoChanged::new(&inventory));
+    }
+
+    let slot = inventory.loadout.current_mut();
+    if slot.is_reloading() {
+        slot.reloading -= time.period.as_secs_f32();
+        if slot.reloading <= 0.0 {
+            inventory.finish_reload();
+            ammo.send(AmmoChanged::new(&inventory));
+        }
+    }
+}
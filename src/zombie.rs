@@ -1,154 +1,285 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-use super::{GAME_WIDTH,GAME_HEIGHT,GameDetails};
 
 use crate::utils::*;
+use crate::collsion_objects::CollisionObject;
 use crate::game::{AnimationTimer, AnimationIndices, OnGameScreen};
+use crate::player::Player;
+use crate::navigation::NavGrid;
+use crate::sound::{PlaySound, Sound};
+use crate::hud::HealthChanged;
+use crate::interpolation::Interpolated;
 
 const ZOMBIE_SPEED: f32 = 150.0;
 
+// How far and how wide a zombie can see
+const SIGHT_RANGE: f32 = 450.0;
+const FIELD_OF_VIEW: f32 = std::f32::consts::PI * 2.0 / 3.0;
+// Close enough to hear or smell the player, whichever way it is facing
+const AWARENESS_RANGE: f32 = 120.0;
+const ATTACK_RANGE: f32 = 80.0;
+// How long a zombie hunts around where it last saw the player before giving up
+const SEARCH_TIME: f32 = 4.0;
+const ZOMBIE_DAMAGE: i32 = 2;
+// Seconds between swipes at the player
+const ATTACK_COOLDOWN: f32 = 1.2;
+// How far the goal can drift (e.g. the player moving) before we plan a new path
+const REPATH_DISTANCE: f32 = 40.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ZombieState {
+    Patrol,
+    Chase,
+    Search,
+    Attack,
+}
+
 #[derive(Component)]
 pub struct Zombie {
     pub pos: Vec2,
+    // Path currently being walked, from the nav grid
     pub loc: Vec::<Vec2>,
     pub cur_loc: usize,
+    pub path_goal: Vec2,
+    // Points to patrol between when there is nobody to chase
+    pub route: Vec::<Vec2>,
+    pub route_index: usize,
     pub hit_box: Vec2,
-    pub health: i32
+    pub health: i32,
+    pub speed: f32,
+    pub state: ZombieState,
+    pub facing: f32,
+    pub last_seen: Vec2,
+    pub search_time: f32,
+    pub attack_cooldown: f32,
+}
+
+impl Zombie {
+    // Starts at the route point before route_index, heading for route_index
+    pub fn new(route: Vec::<Vec2>, route_index: usize) -> Self {
+        let pos = route[(route_index + route.len() - 1) % route.len()];
+        Zombie {
+            pos: pos,
+            loc: Vec::new(),
+            cur_loc: 0,
+            path_goal: pos,
+            route: route,
+            route_index: route_index,
+            hit_box: Vec2::new(100.0,100.0),
+            health: 5,
+            speed: ZOMBIE_SPEED,
+            state: ZombieState::Patrol,
+            facing: 0.0,
+            last_seen: pos,
+            search_time: 0.0,
+            attack_cooldown: 0.0,
+        }
+    }
+
+    pub fn can_see(&self, target: Vec2, objects: &Query<&CollisionObject>) -> bool {
+        let to_target = target - self.pos;
+        let distance = to_target.length();
+        if distance > SIGHT_RANGE {
+            return false;
+        }
+        if distance > AWARENESS_RANGE {
+            let looking = Vec2::new(self.facing.cos(), self.facing.sin());
+            if looking.angle_between(to_target).abs() > FIELD_OF_VIEW / 2.0 {
+                return false;
+            }
+        }
+        !objects.iter().any(|object| object.blocks_line(self.pos, target))
+    }
+}
+
+pub fn zombie_perception(
+    time: Res<FixedTime>,
+    mut zombies: Query<&mut Zombie>,
+    players: Query<&Player>,
+    objects: Query<&CollisionObject>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let player = players.single();
+
+    for mut zombie in &mut zombies {
+        let sees_player = zombie.can_see(player.loc, &objects);
+        if sees_player {
+            zombie.last_seen = player.loc;
+        }
+        let in_reach = zombie.pos.distance(player.loc) <= ATTACK_RANGE;
+
+        let state = zombie.state;
+        let next_state = match state {
+            ZombieState::Patrol if sees_player => ZombieState::Chase,
+            ZombieState::Patrol => ZombieState::Patrol,
+            ZombieState::Chase | ZombieState::Attack | ZombieState::Search if sees_player && in_reach => ZombieState::Attack,
+            ZombieState::Chase | ZombieState::Attack | ZombieState::Search if sees_player => ZombieState::Chase,
+            // Lost them - head for where they were last seen
+            ZombieState::Chase | ZombieState::Attack => {
+                zombie.search_time = SEARCH_TIME;
+                ZombieState::Search
+            }
+            ZombieState::Search => {
+                zombie.search_time -= time.period.as_secs_f32();
+                if zombie.search_time <= 0.0 {
+                    ZombieState::Patrol
+                } else {
+                    ZombieState::Search
+                }
+            }
+        };
+        // The path being walked was for the old state, e.g. giving up a chase mustn't count
+        // as reaching the next patrol point
+        if next_state != state {
+            zombie.loc.clear();
+            zombie.cur_loc = 0;
+        }
+        zombie.state = next_state;
+    }
+}
+
+pub fn zombie_attack(
+    time: Res<FixedTime>,
+    mut zombies: Query<&mut Zombie>,
+    mut players: Query<&mut Player>,
+    mut sounds: EventWriter<PlaySound>,
+    mut health: EventWriter<HealthChanged>,
+){
+    if players.is_empty() {
+        return;
+    }
+    let mut player = players.single_mut();
+
+    for mut zombie in &mut zombies {
+        zombie.attack_cooldown = (zombie.attack_cooldown - time.period.as_secs_f32()).max(0.0);
+        if zombie.state != ZombieState::Attack || zombie.attack_cooldown > 0.0 {
+            continue;
+        }
+        if zombie.pos.distance(player.loc) <= ATTACK_RANGE {
+            if player.take_damage(ZOMBIE_DAMAGE) {
+                sounds.send(PlaySound { sound: Sound::PlayerHurt, at: None });
+                health.send(HealthChanged { health: player.health });
+            }
+            zombie.attack_cooldown = ATTACK_COOLDOWN;
+        }
+    }
 }
 
 pub fn zombie_mover(
-    time: Res<Time>,
+    time: Res<FixedTime>,
     mut zombies: Query<(
         &mut Zombie,
         &mut Transform,
+        &mut Interpolated,
     )>,
-    game_details: Res<GameDetails>
+    objects: Query<&CollisionObject>,
+    nav_grid: Res<NavGrid>,
 ){
-    for (mut zombie, mut transform) in &mut zombies {
-        // Get where we are and where we are heading
-        let target = Vec2::new(zombie.loc[zombie.cur_loc].x, zombie.loc[zombie.cur_loc].y);
+    for (mut zombie, mut transform, mut interpolated) in &mut zombies {
+        let state = zombie.state;
+
+        // Reached the patrol point - on to the next one
+        if state == ZombieState::Patrol && !zombie.loc.is_empty() && zombie.cur_loc >= zombie.loc.len() {
+            zombie.route_index = (zombie.route_index + 1) % zombie.route.len();
+            zombie.loc.clear();
+        }
+
+        // Plan a path to wherever we are trying to get to
+        let goal = match state {
+            ZombieState::Patrol => Some(zombie.route[zombie.route_index]),
+            ZombieState::Chase | ZombieState::Search => Some(zombie.last_seen),
+            ZombieState::Attack => None,
+        };
+        if let Some(goal) = goal {
+            if zombie.loc.is_empty() || zombie.path_goal.distance(goal) > REPATH_DISTANCE {
+                zombie.loc = nav_grid.find_path(zombie.pos, goal).unwrap_or_else(|| vec![goal]);
+                zombie.cur_loc = 0;
+                zombie.path_goal = goal;
+            }
+        }
+
+        // Get where we are heading and what we are looking at
+        let walking = goal.is_some() && zombie.cur_loc < zombie.loc.len();
+        let cur_loc = if walking { zombie.loc[zombie.cur_loc] } else { zombie.pos };
+        let look_at = if state == ZombieState::Attack { zombie.last_seen } else { cur_loc };
         
         // Calculate angle of rotation (plus fiddle factor)
-        let direction = target - zombie.pos;
-        let mut angle_to_target =  normalize_angle(
-            direction.y.atan2(direction.x)
-        );
+        let direction = look_at - zombie.pos;
+        if direction != Vec2::ZERO {
+            zombie.facing = direction.y.atan2(direction.x);
+        }
+        let mut angle_to_target =  normalize_angle(zombie.facing);
 
         angle_to_target = angle_to_target + (std::f32::consts::PI/2.0); // Add 90 degrees because of image rotation
         
         // Now move towards the goal point using the angle of rotation
-        let cur_loc = zombie.loc[zombie.cur_loc];
+        let mut next_pos = zombie.pos;
         
-        let mut x_met = false;
         if cur_loc.x != zombie.pos.x {
-            if (cur_loc.x - zombie.pos.x).abs() <= ZOMBIE_SPEED * time.delta_seconds() {
-                zombie.pos.x = cur_loc.x;
-                x_met = true;
+            if (cur_loc.x - zombie.pos.x).abs() <= zombie.speed * time.period.as_secs_f32() {
+                next_pos.x = cur_loc.x;
             }else{
-                zombie.pos.x += angle_to_target.sin() * (ZOMBIE_SPEED*time.delta_seconds());
+                next_pos.x += angle_to_target.sin() * (zombie.speed*time.period.as_secs_f32());
             }
-        }else{
-            x_met = true;
         }
         
-        let mut y_met = false;
         if cur_loc.y != zombie.pos.y {
-            if (cur_loc.y - zombie.pos.y).abs() <= ZOMBIE_SPEED * time.delta_seconds() {
-                zombie.pos.y = cur_loc.y;
-                y_met = true;
+            if (cur_loc.y - zombie.pos.y).abs() <= zombie.speed * time.period.as_secs_f32() {
+                next_pos.y = cur_loc.y;
             }else{
-                zombie.pos.y -= angle_to_target.cos() * (ZOMBIE_SPEED*time.delta_seconds());
+                next_pos.y -= angle_to_target.cos() * (zombie.speed*time.period.as_secs_f32());
             }
-        }else{
-            y_met = true;
         }
 
-        // Finally apply translation and rotation, taking into account offset
+        // Don't walk through the scenery
+        zombie.pos = check_position_move(zombie.pos, next_pos - zombie.pos, zombie.hit_box, &objects);
+        let x_met = zombie.pos.x == cur_loc.x;
+        let y_met = zombie.pos.y == cur_loc.y;
+
+        // Finally apply translation and rotation
         transform.rotation = Quat::from_rotation_z(angle_to_target);
-        transform.translation.x = zombie.pos.x - game_details.offset_x - (GAME_WIDTH/2.0);
-        transform.translation.y = zombie.pos.y - game_details.offset_y - (GAME_HEIGHT/2.0);
+        interpolated.current = zombie.pos;
 
         // We have hit our mark - move onto next point
-        if x_met && y_met {
+        if walking && x_met && y_met {
             zombie.cur_loc += 1;
-            if zombie.cur_loc >= zombie.loc.len() {
-                zombie.cur_loc = 0;
-            }
         }
 
     }    
 }
 
-
-pub fn zombie_checker(
-    mut commands: Commands,
-    zombies: Query<&Zombie>,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    game_details: Res<GameDetails>
+pub fn spawn_zombie(
+    commands: &mut Commands,
+    texture_atlas_handle: Handle<TextureAtlas>,
+    zombie: Zombie,
 ){
-    if zombies.is_empty() {
-        // Make a zombie
-        let texture_path = Path::new("images").join("zombie").join("zombie.png");
-        let texture_handle = asset_server.load(texture_path);
-        let texture_atlas =
-            TextureAtlas::from_grid(texture_handle, Vec2::new(200.0, 200.0), 4, 1, None, None);
-        let texture_atlas_handle = texture_atlases.add(texture_atlas);
-        let animation_indices = AnimationIndices { first: 0, last: 3 };
-        
-        let mut locations = Vec::<Vec2>::new();
-        locations.push(Vec2::new(400.0, 200.0));
-        locations.push(Vec2::new(250.0, 450.0)); 
-        locations.push(Vec2::new(400.0, 600.0));
-        locations.push(Vec2::new(900.0, 600.0));
-        locations.push(Vec2::new(900.0, 200.0));
-        
-        commands.spawn((
-            SpriteSheetBundle {
-                texture_atlas: texture_atlas_handle.clone(),
-                sprite: TextureAtlasSprite::new(animation_indices.first),
-                transform: Transform::from_xyz(
-                    locations[0].x - game_details.offset_x - (GAME_WIDTH/2.0),
-                    locations[0].y - game_details.offset_y - (GAME_HEIGHT/2.0),
-                    2.0
-                ).with_scale(Vec3::splat(0.5)),
-                ..default()
-            },
-            animation_indices,
-            AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        ))
-        .insert(Zombie{
-            pos: Vec2::new(locations[0].x, locations[0].y),
-            loc: locations.clone(),
-            cur_loc: 1,
-            hit_box: Vec2::new(100.0,100.0),
-            health: 5
-        })
-        .insert(OnGameScreen);
-
-        let animation_indices = AnimationIndices { first: 0, last: 3 };
-        commands.spawn((
-            SpriteSheetBundle {
-                texture_atlas: texture_atlas_handle,
-                sprite: TextureAtlasSprite::new(animation_indices.first),
-                transform: Transform::from_xyz(
-                    locations[3].x - game_details.offset_x - (GAME_WIDTH/2.0),
-                    locations[3].y - game_details.offset_y - (GAME_HEIGHT/2.0),
-                    2.0
-                ).with_scale(Vec3::splat(0.5)),
-                ..default()
-            },
-            animation_indices,
-            AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
-        ))
-        .insert(Zombie{
-            pos: Vec2::new(locations[3].x, locations[3].y),
-            loc: locations,
-            cur_loc: 4,
-            hit_box: Vec2::new(100.0,100.0),
-            health: 5
-        })
-        .insert(OnGameScreen);
-    }
+    let animation_indices = AnimationIndices { first: 0, last: 3 };
+    commands.spawn((
+        SpriteSheetBundle {
+            texture_atlas: texture_atlas_handle,
+            sprite: TextureAtlasSprite::new(animation_indices.first),
+            transform: Transform::from_xyz(zombie.pos.x, zombie.pos.y, 2.0).with_scale(Vec3::splat(0.5)),
+            ..default()
+        },
+        animation_indices,
+        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+    ))
+    .insert(Interpolated::new(zombie.pos))
+    .insert(zombie)
+    .insert(OnGameScreen);
+}
+
+pub fn zombie_texture_atlas(
+    asset_server: &Res<AssetServer>,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+) -> Handle<TextureAtlas> {
+    let texture_path = Path::new("images").join("zombie").join("zombie.png");
+    let texture_handle = asset_server.load(texture_path);
+    let texture_atlas =
+        TextureAtlas::from_grid(texture_handle, Vec2::new(200.0, 200.0), 4, 1, None, None);
+    texture_atlases.add(texture_atlas)
 }